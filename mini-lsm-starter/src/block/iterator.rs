@@ -73,7 +73,7 @@ impl BlockIterator {
         if idx >= self.block.offsets.len() {
             self.key.clear();
             self.value.clear();
-            self.idx = std::usize::MAX;
+            self.idx = usize::MAX;
             return;
         }
         self.idx = idx;
@@ -1,6 +1,6 @@
 use std::cmp::{self};
-use std::collections::BinaryHeap;
 use std::collections::binary_heap::PeekMut;
+use std::collections::BinaryHeap;
 use std::debug_assert;
 
 use anyhow::Result;
@@ -19,18 +19,17 @@ impl<I: StorageIterator> Eq for HeapWrapper<I> {}
 
 impl<I: StorageIterator> PartialOrd for HeapWrapper<I> {
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
-        match self.1.key().cmp(other.1.key()) {
-            cmp::Ordering::Greater => Some(cmp::Ordering::Greater),
-            cmp::Ordering::Less => Some(cmp::Ordering::Less),
-            cmp::Ordering::Equal => self.0.partial_cmp(&other.0),
-        }
-        .map(|x| x.reverse())
+        Some(self.cmp(other))
     }
 }
 
 impl<I: StorageIterator> Ord for HeapWrapper<I> {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+        self.1
+            .key()
+            .cmp(other.1.key())
+            .then(self.0.cmp(&other.0))
+            .reverse()
     }
 }
 
@@ -59,9 +58,9 @@ impl<I: StorageIterator> MergeIterator<I> {
             current = Some(heap.pop().unwrap());
         }
 
-         Self {
+        Self {
             iters: heap,
-            current: current,
+            current,
         }
     }
 }
@@ -80,7 +79,7 @@ impl<I: StorageIterator> StorageIterator for MergeIterator<I> {
     fn is_valid(&self) -> bool {
         match &self.current {
             Some(w) => w.1.is_valid(),
-            _ => false
+            _ => false,
         }
     }
 
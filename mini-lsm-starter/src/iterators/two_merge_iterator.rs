@@ -14,15 +14,12 @@ pub struct TwoMergeIterator<A: StorageIterator, B: StorageIterator> {
 
 impl<A: StorageIterator, B: StorageIterator> TwoMergeIterator<A, B> {
     pub fn create(a: A, b: B) -> Result<Self> {
-        Ok(Self {
-            a: a,
-            b: b,
-        })
+        Ok(Self { a, b })
     }
 
     fn prefer_a(&self) -> bool {
         if self.a.is_valid() & self.b.is_valid() {
-            self.a.key() <= self.b.key()     
+            self.a.key() <= self.b.key()
         } else {
             self.a.is_valid()
         }
@@ -33,18 +30,18 @@ impl<A: StorageIterator, B: StorageIterator> StorageIterator for TwoMergeIterato
     fn key(&self) -> &[u8] {
         debug_assert!(self.is_valid(), "invalid iterator");
         if self.prefer_a() {
-            &self.a.key()[..]
+            self.a.key()
         } else {
-            &self.b.key()[..]
+            self.b.key()
         }
     }
 
     fn value(&self) -> &[u8] {
         debug_assert!(self.is_valid(), "invalid iterator");
         if self.prefer_a() {
-            &self.a.value()[..]
+            self.a.value()
         } else {
-            &self.b.value()[..]
+            self.b.value()
         }
     }
 
@@ -65,7 +62,7 @@ impl<A: StorageIterator, B: StorageIterator> StorageIterator for TwoMergeIterato
                 self.b.next()
             } else if self.a.key() < self.b.key() {
                 self.a.next()
-            } else { 
+            } else {
                 self.b.next()
             }
         }
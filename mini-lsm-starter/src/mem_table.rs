@@ -45,7 +45,7 @@ impl MemTable {
     }
 
     /// Get an iterator over a range of keys.
-    pub fn scan(&self, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> MemTableIterator {
+    pub fn scan(&self, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> MemTableIterator<'_> {
         let (lower, upper) = (map_bound(lower), map_bound(upper));
         let mut iter = MemTableIterator {
             map: self.map.clone(),
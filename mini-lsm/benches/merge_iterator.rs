@@ -0,0 +1,80 @@
+//! Compares `MergeIterator` and `LoserTreeIterator` on many-way merges of memtables. Run with
+//! `cargo bench -p mini-lsm --bench merge_iterator`.
+
+use std::ops::Bound;
+use std::time::{Duration, Instant};
+
+use mini_lsm::iterators::loser_tree_iterator::LoserTreeIterator;
+use mini_lsm::iterators::merge_iterator::MergeIterator;
+use mini_lsm::iterators::StorageIterator;
+use mini_lsm::mem_table::{MemTable, MemTableIterator};
+
+const KEYS_PER_MEMTABLE: usize = 10000;
+const ROUNDS: usize = 5;
+
+/// Build memtables with interleaved keys, where every fourth key is also in the next memtable.
+fn generate_memtables(num_of_memtables: usize) -> Vec<MemTable> {
+    (0..num_of_memtables)
+        .map(|table_idx| {
+            let memtable = MemTable::create();
+            for idx in 0..KEYS_PER_MEMTABLE {
+                let key = idx * num_of_memtables + table_idx;
+                memtable.put(format!("key_{:010}", key).as_bytes(), b"value");
+                if idx % 4 == 0 {
+                    memtable.put(format!("key_{:010}", key + 1).as_bytes(), b"value");
+                }
+            }
+            memtable
+        })
+        .collect()
+}
+
+fn scan_all(memtables: &[MemTable]) -> impl Iterator<Item = Box<MemTableIterator>> + '_ {
+    memtables
+        .iter()
+        .map(|memtable| Box::new(memtable.scan(Bound::Unbounded, Bound::Unbounded)))
+}
+
+/// Run `merge` for a few rounds, and return the best time and the number of merged entries.
+fn bench(mut merge: impl FnMut() -> usize) -> (Duration, usize) {
+    let mut best = Duration::MAX;
+    let mut count = 0;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        count = merge();
+        best = best.min(start.elapsed());
+    }
+    (best, count)
+}
+
+fn count(mut iter: impl StorageIterator) -> usize {
+    let mut count = 0;
+    while iter.is_valid() {
+        count += 1;
+        iter.next().unwrap();
+    }
+    count
+}
+
+fn main() {
+    println!(
+        "{:>6} {:>10} {:>12} {:>12} {:>8}",
+        "ways", "entries", "heap", "loser tree", "speedup"
+    );
+    for num_of_memtables in [2, 8, 32, 64] {
+        let memtables = generate_memtables(num_of_memtables);
+        let (heap, heap_count) =
+            bench(|| count(MergeIterator::create(scan_all(&memtables).collect())));
+        let (loser_tree, loser_tree_count) =
+            bench(|| count(LoserTreeIterator::create(scan_all(&memtables).collect())));
+        assert_eq!(heap_count, loser_tree_count);
+        println!(
+            "{:>6} {:>10} {:>12?} {:>12?} {:>7.2}x",
+            num_of_memtables,
+            heap_count,
+            heap,
+            loser_tree,
+            heap.as_secs_f64() / loser_tree.as_secs_f64()
+        );
+    }
+}
@@ -1,41 +1,167 @@
 mod builder;
 mod iterator;
 
+use std::ops::Range;
+
 pub use builder::BlockBuilder;
 use bytes::{Buf, BufMut, Bytes};
 pub use iterator::BlockIterator;
 
+use crate::comparator::Comparator;
+use crate::hash::key_hash;
+
 pub const SIZEOF_U16: usize = std::mem::size_of::<u16>();
 
-/// A block is the smallest unit of read and caching in LSM tree. It is a collection of sorted
-/// key-value pairs.
+/// Set on the number of elements at the end of a block if the block has a hash index. A block
+/// can never hold this many entries, as offsets are `u16` and each entry takes at least 5 bytes.
+const HASH_INDEX_FLAG: u16 = 1 << 15;
+/// A hash index bucket that no key hashes to.
+const BUCKET_EMPTY: u16 = u16::MAX;
+/// A hash index bucket that more than one key hashes to.
+const BUCKET_COLLISION: u16 = u16::MAX - 1;
+
+/// Number of buckets in the hash index of a block with `num_of_entries` entries.
+pub(crate) fn hash_index_buckets(num_of_entries: usize) -> usize {
+    num_of_entries * 4 / 3 + 1
+}
+
+/// A block is the smallest unit of read and caching in LSM tree. It is a collection of key-value
+/// pairs, sorted by the comparator of the storage.
+///
+/// A block may carry a hash index after the offsets, which maps the hash of each key to the index
+/// of its entry so that point lookups can skip the binary search.
+///
+/// The entries are kept in the buffer the block is decoded from, and values are handed out as
+/// slices of it without copying.
 pub struct Block {
-    data: Vec<u8>,
+    data: Bytes,
     offsets: Vec<u16>,
+    hash_index: Option<Vec<u16>>,
 }
 
 impl Block {
     pub fn encode(&self) -> Bytes {
-        let mut buf = self.data.clone();
+        let mut buf = self.data.to_vec();
         let offsets_len = self.offsets.len();
+        debug_assert!((offsets_len as u16) < HASH_INDEX_FLAG);
         for offset in &self.offsets {
             buf.put_u16(*offset);
         }
-        // Adds number of elements at the end of the block
-        buf.put_u16(offsets_len as u16);
+        if let Some(ref buckets) = self.hash_index {
+            for bucket in buckets {
+                buf.put_u16(*bucket);
+            }
+            buf.put_u16(buckets.len() as u16);
+            buf.put_u16(offsets_len as u16 | HASH_INDEX_FLAG);
+        } else {
+            // Adds number of elements at the end of the block
+            buf.put_u16(offsets_len as u16);
+        }
         buf.into()
     }
 
-    pub fn decode(data: &[u8]) -> Self {
-        let entry_offsets_len = (&data[data.len() - SIZEOF_U16..]).get_u16() as usize;
-        let data_end = data.len() - SIZEOF_U16 - entry_offsets_len * SIZEOF_U16;
-        let offsets_raw = &data[data_end..data.len() - SIZEOF_U16];
+    pub fn decode(data: Bytes) -> Self {
+        let raw_offsets_len = (&data[data.len() - SIZEOF_U16..]).get_u16();
+        let entry_offsets_len = (raw_offsets_len & !HASH_INDEX_FLAG) as usize;
+        let mut offsets_end = data.len() - SIZEOF_U16;
+        let hash_index = if raw_offsets_len & HASH_INDEX_FLAG != 0 {
+            let num_buckets = (&data[offsets_end - SIZEOF_U16..]).get_u16() as usize;
+            let buckets_end = offsets_end - SIZEOF_U16;
+            offsets_end = buckets_end - num_buckets * SIZEOF_U16;
+            let buckets = data[offsets_end..buckets_end]
+                .chunks(SIZEOF_U16)
+                .map(|mut x| x.get_u16())
+                .collect();
+            Some(buckets)
+        } else {
+            None
+        };
+        let data_end = offsets_end - entry_offsets_len * SIZEOF_U16;
+        let offsets_raw = &data[data_end..offsets_end];
         let offsets = offsets_raw
             .chunks(SIZEOF_U16)
             .map(|mut x| x.get_u16())
             .collect();
-        let data = data[0..data_end].to_vec();
-        Self { data, offsets }
+        let data = data.slice(0..data_end);
+        Self {
+            data,
+            offsets,
+            hash_index,
+        }
+    }
+
+    /// Build the hash index of the block.
+    fn build_hash_index(&self) -> Vec<u16> {
+        let num_buckets = hash_index_buckets(self.offsets.len());
+        let mut buckets = vec![BUCKET_EMPTY; num_buckets];
+        for idx in 0..self.offsets.len() {
+            let (key, _) = self.entry(idx);
+            let bucket = &mut buckets[(key_hash(key) % num_buckets as u64) as usize];
+            *bucket = if *bucket == BUCKET_EMPTY {
+                idx as u16
+            } else {
+                BUCKET_COLLISION
+            };
+        }
+        buckets
+    }
+
+    /// Check if the block has a hash index.
+    pub fn has_hash_index(&self) -> bool {
+        self.hash_index.is_some()
+    }
+
+    /// Find the entry of exactly `key`. The hash index is used if the block has one, and binary
+    /// search in the order of `comparator` is the fallback when there is no hash index or the
+    /// bucket has a collision.
+    pub fn find_entry(&self, key: &[u8], comparator: &dyn Comparator) -> Option<usize> {
+        if let Some(ref buckets) = self.hash_index {
+            match buckets[(key_hash(key) % buckets.len() as u64) as usize] {
+                BUCKET_EMPTY => return None,
+                BUCKET_COLLISION => {}
+                idx => return (self.entry(idx as usize).0 == key).then_some(idx as usize),
+            }
+        }
+        let (mut low, mut high) = (0, self.offsets.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match comparator.compare(self.entry(mid).0, key) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
+
+    /// Get the number of key-value pairs in the block.
+    pub fn num_of_entries(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Get the ranges of the key and the value of the idx-th entry in the data.
+    fn entry_ranges(&self, idx: usize) -> (Range<usize>, Range<usize>) {
+        let offset = self.offsets[idx] as usize;
+        let mut entry = &self.data[offset..];
+        let key_len = entry.get_u16() as usize;
+        let key_start = offset + SIZEOF_U16;
+        let key_end = key_start + key_len;
+        entry.advance(key_len);
+        let value_len = entry.get_u16() as usize;
+        let value_start = key_end + SIZEOF_U16;
+        (key_start..key_end, value_start..value_start + value_len)
+    }
+
+    /// Get the key and value of the idx-th entry.
+    pub fn entry(&self, idx: usize) -> (&[u8], &[u8]) {
+        let (key, value) = self.entry_ranges(idx);
+        (&self.data[key], &self.data[value])
+    }
+
+    /// Get the value of the idx-th entry, sharing the buffer of the block.
+    pub fn value_bytes(&self, idx: usize) -> Bytes {
+        let (_, value) = self.entry_ranges(idx);
+        self.data.slice(value)
     }
 }
 
@@ -1,6 +1,6 @@
 use bytes::BufMut;
 
-use super::{Block, SIZEOF_U16};
+use super::{hash_index_buckets, Block, SIZEOF_U16};
 
 /// Builds a block.
 pub struct BlockBuilder {
@@ -10,6 +10,8 @@ pub struct BlockBuilder {
     data: Vec<u8>,
     /// The expected block size.
     block_size: usize,
+    /// Whether to build a hash index for point lookups.
+    hash_index: bool,
 }
 
 impl BlockBuilder {
@@ -19,19 +21,42 @@ impl BlockBuilder {
             offsets: Vec::new(),
             data: Vec::new(),
             block_size,
+            hash_index: false,
+        }
+    }
+
+    /// Build a hash index in the block for point lookups.
+    pub fn with_hash_index(mut self) -> Self {
+        self.hash_index = true;
+        self
+    }
+
+    fn hash_index_size(&self, num_of_entries: usize) -> usize {
+        if self.hash_index {
+            // The buckets, and the number of buckets
+            hash_index_buckets(num_of_entries) * SIZEOF_U16 + SIZEOF_U16
+        } else {
+            0
         }
     }
 
     fn estimated_size(&self) -> usize {
-        self.offsets.len() * SIZEOF_U16 + self.data.len() + SIZEOF_U16
+        self.offsets.len() * SIZEOF_U16
+            + self.data.len()
+            + SIZEOF_U16
+            + self.hash_index_size(self.offsets.len())
     }
 
     /// Adds a key-value pair to the block. Returns false when the block is full.
     #[must_use]
     pub fn add(&mut self, key: &[u8], value: &[u8]) -> bool {
         assert!(!key.is_empty(), "key must not be empty");
-        // The overhead here is `key_len` + `val_len` + `offset`, each is of type `u16`
-        if self.estimated_size() + key.len() + value.len() + SIZEOF_U16 * 3 > self.block_size
+        // The overhead here is `key_len` + `val_len` + `offset`, each is of type `u16`, and the
+        // growth of the hash index
+        let hash_index_growth =
+            self.hash_index_size(self.offsets.len() + 1) - self.hash_index_size(self.offsets.len());
+        if self.estimated_size() + key.len() + value.len() + SIZEOF_U16 * 3 + hash_index_growth
+            > self.block_size
             && !self.is_empty()
         {
             return false;
@@ -55,9 +80,14 @@ impl BlockBuilder {
         if self.is_empty() {
             panic!("block should not be empty");
         }
-        Block {
-            data: self.data,
+        let mut block = Block {
+            data: self.data.into(),
             offsets: self.offsets,
+            hash_index: None,
+        };
+        if self.hash_index {
+            block.hash_index = Some(block.build_hash_index());
         }
+        block
     }
 }
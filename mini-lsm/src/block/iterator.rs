@@ -1,37 +1,51 @@
+use std::ops::Range;
 use std::sync::Arc;
 
-use bytes::Buf;
+use bytes::Bytes;
 
 use super::Block;
+use crate::comparator::{self, Comparator};
 
-/// Iterates on a block.
+/// Iterates on a block. The current entry is kept as ranges into the data of the block, so moving
+/// the iterator does not copy keys or values.
 pub struct BlockIterator {
     block: Arc<Block>,
-    key: Vec<u8>,
-    value: Vec<u8>,
+    comparator: Arc<dyn Comparator>,
+    /// The range of the current key, which is empty if the iterator is invalid.
+    key: Range<usize>,
+    value: Range<usize>,
     idx: usize,
 }
 
 impl BlockIterator {
-    fn new(block: Arc<Block>) -> Self {
+    fn new(block: Arc<Block>, comparator: Arc<dyn Comparator>) -> Self {
         Self {
             block,
-            key: Vec::new(),
-            value: Vec::new(),
+            comparator,
+            key: 0..0,
+            value: 0..0,
             idx: 0,
         }
     }
 
     /// Creates a block iterator and seek to the first entry.
     pub fn create_and_seek_to_first(block: Arc<Block>) -> Self {
-        let mut iter = Self::new(block);
+        Self::create_and_seek_to_first_with_comparator(block, comparator::bytewise())
+    }
+
+    /// Creates a block iterator over a block sorted by `comparator` and seek to the first entry.
+    pub fn create_and_seek_to_first_with_comparator(
+        block: Arc<Block>,
+        comparator: Arc<dyn Comparator>,
+    ) -> Self {
+        let mut iter = Self::new(block, comparator);
         iter.seek_to_first();
         iter
     }
 
     /// Creates a block iterator and seek to the first key that >= `key`.
     pub fn create_and_seek_to_key(block: Arc<Block>, key: &[u8]) -> Self {
-        let mut iter = Self::new(block);
+        let mut iter = Self::new(block, comparator::bytewise());
         iter.seek_to_key(key);
         iter
     }
@@ -39,13 +53,25 @@ impl BlockIterator {
     /// Returns the key of the current entry.
     pub fn key(&self) -> &[u8] {
         debug_assert!(!self.key.is_empty(), "invalid iterator");
-        &self.key
+        &self.block.data[self.key.clone()]
     }
 
     /// Returns the value of the current entry.
     pub fn value(&self) -> &[u8] {
         debug_assert!(!self.key.is_empty(), "invalid iterator");
-        &self.value
+        &self.block.data[self.value.clone()]
+    }
+
+    /// Returns the key of the current entry, sharing the buffer of the block.
+    pub fn key_bytes(&self) -> Bytes {
+        debug_assert!(!self.key.is_empty(), "invalid iterator");
+        self.block.data.slice(self.key.clone())
+    }
+
+    /// Returns the value of the current entry, sharing the buffer of the block.
+    pub fn value_bytes(&self) -> Bytes {
+        debug_assert!(!self.key.is_empty(), "invalid iterator");
+        self.block.data.slice(self.value.clone())
     }
 
     /// Returns true if the iterator is valid.
@@ -61,12 +87,11 @@ impl BlockIterator {
     /// Seeks to the idx-th key in the block.
     fn seek_to(&mut self, idx: usize) {
         if idx >= self.block.offsets.len() {
-            self.key.clear();
-            self.value.clear();
+            self.key = 0..0;
+            self.value = 0..0;
             return;
         }
-        let offset = self.block.offsets[idx] as usize;
-        self.seek_to_offset(offset);
+        (self.key, self.value) = self.block.entry_ranges(idx);
         self.idx = idx;
     }
 
@@ -76,25 +101,7 @@ impl BlockIterator {
         self.seek_to(self.idx);
     }
 
-    /// Seek to the specified position and update the current `key` and `value`
-    /// Index update will be handled by caller
-    fn seek_to_offset(&mut self, offset: usize) {
-        let mut entry = &self.block.data[offset..];
-        // Since `get_u16()` will automatically move the ptr 2 bytes ahead here,
-        // we don't need to manually advance it
-        let key_len = entry.get_u16() as usize;
-        let key = entry[..key_len].to_vec();
-        entry.advance(key_len);
-        self.key.clear();
-        self.key.extend(key);
-        let value_len = entry.get_u16() as usize;
-        let value = entry[..value_len].to_vec();
-        entry.advance(value_len);
-        self.value.clear();
-        self.value.extend(value);
-    }
-
-    /// Seek to the first key that is >= `key`.
+    /// Seek to the first key that is >= `key`, in the order of the comparator of the iterator.
     pub fn seek_to_key(&mut self, key: &[u8]) {
         let mut low = 0;
         let mut high = self.block.offsets.len();
@@ -102,7 +109,7 @@ impl BlockIterator {
             let mid = low + (high - low) / 2;
             self.seek_to(mid);
             assert!(self.is_valid());
-            match self.key().cmp(key) {
+            match self.comparator.compare(self.key(), key) {
                 std::cmp::Ordering::Less => low = mid + 1,
                 std::cmp::Ordering::Greater => high = mid,
                 std::cmp::Ordering::Equal => return,
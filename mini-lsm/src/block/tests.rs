@@ -3,6 +3,7 @@ use std::sync::Arc;
 use super::builder::BlockBuilder;
 use super::iterator::BlockIterator;
 use super::*;
+use crate::comparator::{BytewiseComparator, ReverseBytewiseComparator};
 
 #[test]
 fn test_block_build_single_key() {
@@ -56,7 +57,7 @@ fn test_block_encode() {
 fn test_block_decode() {
     let block = generate_block();
     let encoded = block.encode();
-    let decoded_block = Block::decode(&encoded);
+    let decoded_block = Block::decode(encoded);
     assert_eq!(block.offsets, decoded_block.offsets);
     assert_eq!(block.data, decoded_block.data);
 }
@@ -120,3 +121,88 @@ fn test_block_seek_key() {
         iter.seek_to_key(b"k");
     }
 }
+
+#[test]
+fn test_block_hash_index() {
+    let mut builder = BlockBuilder::new(10000).with_hash_index();
+    for idx in 0..num_of_keys() {
+        assert!(builder.add(&key_of(idx), &value_of(idx)));
+    }
+    let block = builder.build();
+    assert!(block.has_hash_index());
+    let decoded_block = Block::decode(block.encode());
+    assert!(decoded_block.has_hash_index());
+    assert_eq!(block.offsets, decoded_block.offsets);
+    assert_eq!(block.data, decoded_block.data);
+    assert_eq!(block.hash_index, decoded_block.hash_index);
+    for idx in 0..num_of_keys() {
+        assert_eq!(
+            decoded_block.find_entry(&key_of(idx), &BytewiseComparator),
+            Some(idx)
+        );
+        assert_eq!(
+            decoded_block.find_entry(
+                format!("key_{:03}", idx * 5 + 1).as_bytes(),
+                &BytewiseComparator
+            ),
+            None
+        );
+    }
+    let mut iter = BlockIterator::create_and_seek_to_key(Arc::new(decoded_block), b"key_006");
+    assert_eq!(iter.key(), key_of(2));
+    iter.next();
+    assert_eq!(iter.key(), key_of(3));
+}
+
+#[test]
+fn test_block_find_entry_without_hash_index() {
+    let block = generate_block();
+    assert!(!block.has_hash_index());
+    for idx in 0..num_of_keys() {
+        assert_eq!(
+            block.find_entry(&key_of(idx), &BytewiseComparator),
+            Some(idx)
+        );
+    }
+    assert_eq!(block.find_entry(b"key_001", &BytewiseComparator), None);
+}
+
+#[test]
+fn test_block_seek_key_with_comparator() {
+    let mut builder = BlockBuilder::new(10000);
+    for idx in (0..100).rev() {
+        assert!(builder.add(&key_of(idx), &value_of(idx)));
+    }
+    let block = Arc::new(builder.build());
+    let mut iter = BlockIterator::create_and_seek_to_first_with_comparator(
+        block.clone(),
+        Arc::new(ReverseBytewiseComparator),
+    );
+    for idx in (0..100).rev() {
+        iter.seek_to_key(format!("key_{:03}", idx * 5 + 1).as_bytes());
+        assert!(iter.is_valid());
+        assert_eq!(iter.key(), key_of(idx));
+        assert_eq!(
+            block.find_entry(&key_of(idx), &ReverseBytewiseComparator),
+            Some(99 - idx)
+        );
+    }
+    iter.seek_to_key(b"k");
+    assert!(!iter.is_valid());
+}
+
+#[test]
+fn test_block_iterator_zero_copy() {
+    let block = Arc::new(Block::decode(generate_block().encode()));
+    let mut iter = BlockIterator::create_and_seek_to_first(block.clone());
+    for idx in 0..num_of_keys() {
+        let (key, value) = block.entry(idx);
+        assert_eq!(iter.key().as_ptr(), key.as_ptr());
+        assert_eq!(iter.key_bytes().as_ptr(), key.as_ptr());
+        assert_eq!(iter.value_bytes().as_ptr(), value.as_ptr());
+        assert_eq!(block.value_bytes(idx).as_ptr(), value.as_ptr());
+        assert_eq!(iter.value_bytes(), value_of(idx));
+        iter.next();
+    }
+    assert!(!iter.is_valid());
+}
@@ -0,0 +1,78 @@
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::sync::{Arc, LazyLock};
+
+/// Defines the order of keys in memtables, blocks, SSTs and iterators.
+///
+/// The order must be total, and only identical keys may compare as equal, as hash indexes and
+/// bloom filters look up keys by their bytes. The comparator is chosen when the storage is opened
+/// and its name is persisted, so the name must change whenever the order changes.
+pub trait Comparator: Debug + Send + Sync {
+    /// The name of the comparator, persisted with the storage.
+    fn name(&self) -> String;
+
+    /// Compare two keys.
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering;
+}
+
+/// Orders keys lexicographically by their bytes. This is the default.
+#[derive(Clone, Debug, Default)]
+pub struct BytewiseComparator;
+
+impl Comparator for BytewiseComparator {
+    fn name(&self) -> String {
+        "bytewise".to_string()
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+/// Orders keys lexicographically by their bytes, from the largest to the smallest, e.g., for keys
+/// holding timestamps that should be read from the newest.
+#[derive(Clone, Debug, Default)]
+pub struct ReverseBytewiseComparator;
+
+impl Comparator for ReverseBytewiseComparator {
+    fn name(&self) -> String {
+        "reverse-bytewise".to_string()
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        b.cmp(a)
+    }
+}
+
+/// Orders 8-byte keys as little-endian `u64`s. Keys of other lengths are ordered after them, by
+/// their bytes.
+#[derive(Clone, Debug, Default)]
+pub struct U64LittleEndianComparator;
+
+impl Comparator for U64LittleEndianComparator {
+    fn name(&self) -> String {
+        "u64-le".to_string()
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        match (<[u8; 8]>::try_from(a), <[u8; 8]>::try_from(b)) {
+            (Ok(a), Ok(b)) => u64::from_le_bytes(a).cmp(&u64::from_le_bytes(b)),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => a.cmp(b),
+        }
+    }
+}
+
+static BYTEWISE: LazyLock<Arc<dyn Comparator>> = LazyLock::new(|| Arc::new(BytewiseComparator));
+
+/// The default comparator, which orders keys by their bytes.
+pub fn bytewise() -> Arc<dyn Comparator> {
+    BYTEWISE.clone()
+}
+
+/// Check if the comparator orders keys by their bytes, which the range filter and prefix scans
+/// rely on.
+pub fn is_bytewise(comparator: &dyn Comparator) -> bool {
+    comparator.name() == BytewiseComparator.name()
+}
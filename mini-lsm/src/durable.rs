@@ -0,0 +1,82 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+/// The suffix of files being written, which are left behind if the writer crashes.
+pub const TEMP_SUFFIX: &str = ".tmp";
+
+/// The temporary name `path` is written to before it is renamed into place.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(TEMP_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// `fsync` a directory, which persists the files created, renamed or removed in it.
+pub fn sync_dir(dir: &Path) -> Result<()> {
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .with_context(|| format!("failed to sync directory {}", dir.display()))
+}
+
+/// The directory `path` is in.
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Create a directory and its missing parents, and `fsync` the parent of each directory created,
+/// so that the directory survives a crash.
+pub fn create_dir_all(dir: &Path) -> Result<()> {
+    if dir.is_dir() {
+        return Ok(());
+    }
+    let parent = parent_dir(dir);
+    create_dir_all(parent)?;
+    match std::fs::create_dir(dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => {
+            return Err(e).with_context(|| format!("failed to create {}", dir.display()));
+        }
+        _ => {}
+    }
+    sync_dir(parent)
+}
+
+/// Rename a written and `fsync`ed temporary file to `path`, and `fsync` the directory.
+pub fn install(temp_path: &Path, path: &Path) -> Result<()> {
+    std::fs::rename(temp_path, path)
+        .with_context(|| format!("failed to rename {}", temp_path.display()))?;
+    sync_dir(parent_dir(path))
+}
+
+/// Write a file durably: the data is written to a temporary name and `fsync`ed, then renamed to
+/// `path`, and then the directory is `fsync`ed, so that after a crash the file is either complete
+/// or missing. Files written incrementally, such as SSTs, a manifest or a WAL, follow the same
+/// steps with `temp_path` and `install`.
+pub fn write(path: &Path, data: &[u8]) -> Result<()> {
+    let temp_path = temp_path(path);
+    let mut file = File::create(&temp_path)
+        .with_context(|| format!("failed to create {}", temp_path.display()))?;
+    file.write_all(data)
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("failed to write {}", temp_path.display()))?;
+    drop(file);
+    install(&temp_path, path)
+}
+
+/// Remove the temporary files left behind in `dir` by writers that crashed.
+pub fn remove_temp_files(dir: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.to_string_lossy().ends_with(TEMP_SUFFIX) {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
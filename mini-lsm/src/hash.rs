@@ -0,0 +1,16 @@
+/// A 64-bit hash of a key. Unlike `std::hash::DefaultHasher`, its output is stable across Rust
+/// versions and platforms, so it can be persisted in SSTs by hash indexes and filters.
+pub fn key_hash(key: &[u8]) -> u64 {
+    // FNV-1a, followed by the finalizer of MurmurHash3 to spread the bits.
+    let mut h: u64 = 0xcbf29ce484222325;
+    for &b in key {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+    h ^= h >> 33;
+    h
+}
@@ -1,6 +1,10 @@
+pub mod concat_iterator;
+pub mod loser_tree_iterator;
 pub mod merge_iterator;
 pub mod two_merge_iterator;
 
+use bytes::Bytes;
+
 pub trait StorageIterator {
     /// Get the current value.
     fn value(&self) -> &[u8];
@@ -8,6 +12,18 @@ pub trait StorageIterator {
     /// Get the current key.
     fn key(&self) -> &[u8];
 
+    /// Get the current value as `Bytes`. Iterators holding their entries in `Bytes` return a
+    /// reference-counted slice instead of a copy.
+    fn value_bytes(&self) -> Bytes {
+        Bytes::copy_from_slice(self.value())
+    }
+
+    /// Get the current key as `Bytes`. Iterators holding their entries in `Bytes` return a
+    /// reference-counted slice instead of a copy.
+    fn key_bytes(&self) -> Bytes {
+        Bytes::copy_from_slice(self.key())
+    }
+
     /// Check if the current iterator is valid.
     fn is_valid(&self) -> bool;
 
@@ -15,5 +31,11 @@ pub trait StorageIterator {
     fn next(&mut self) -> anyhow::Result<()>;
 }
 
+/// An iterator that can be repositioned.
+pub trait SeekableIterator: StorageIterator {
+    /// Seek to the first key that is >= `key`.
+    fn seek_to_key(&mut self, key: &[u8]) -> anyhow::Result<()>;
+}
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use bytes::Bytes;
+
+use super::{SeekableIterator, StorageIterator};
+use crate::table::{SsTable, SsTableIterator};
+
+/// Concatenates the iterators of SSTs that are sorted by key range and do not overlap, e.g., the
+/// SSTs of a level. Only one SST is iterated at a time, and the iterator of the next SST is created
+/// when the current one is exhausted.
+pub struct SstConcatIterator {
+    current: Option<SsTableIterator>,
+    next_sst_idx: usize,
+    sstables: Vec<Arc<SsTable>>,
+}
+
+impl SstConcatIterator {
+    /// Create a new iterator over `sstables` and seek to the first key-value pair.
+    pub fn create_and_seek_to_first(sstables: Vec<Arc<SsTable>>) -> Result<Self> {
+        let mut iter = Self {
+            current: None,
+            next_sst_idx: 0,
+            sstables,
+        };
+        iter.seek_to_first()?;
+        Ok(iter)
+    }
+
+    /// Create a new iterator over `sstables` and seek to the first key-value pair which >= `key`.
+    pub fn create_and_seek_to_key(sstables: Vec<Arc<SsTable>>, key: &[u8]) -> Result<Self> {
+        let mut iter = Self {
+            current: None,
+            next_sst_idx: 0,
+            sstables,
+        };
+        iter.seek_to_key(key)?;
+        Ok(iter)
+    }
+
+    /// Seek to the first key-value pair.
+    pub fn seek_to_first(&mut self) -> Result<()> {
+        self.current = None;
+        self.next_sst_idx = 0;
+        self.move_until_valid()
+    }
+
+    /// Seek to the first key-value pair which >= `key`. The SST that may contain `key` is found by
+    /// a binary search on the first keys of the SSTs.
+    pub fn seek_to_key(&mut self, key: &[u8]) -> Result<()> {
+        let sst_idx = self
+            .sstables
+            .partition_point(|table| table.comparator().compare(table.first_key(), key).is_le())
+            .saturating_sub(1);
+        self.current = match self.sstables.get(sst_idx) {
+            Some(table) => Some(SsTableIterator::create_and_seek_to_key(table.clone(), key)?),
+            None => None,
+        };
+        self.next_sst_idx = sst_idx + 1;
+        self.move_until_valid()
+    }
+
+    /// Open the next SSTs until the current iterator is valid or there are no more SSTs.
+    fn move_until_valid(&mut self) -> Result<()> {
+        while !self.current.as_ref().is_some_and(|iter| iter.is_valid()) {
+            let Some(table) = self.sstables.get(self.next_sst_idx) else {
+                break;
+            };
+            self.current = Some(SsTableIterator::create_and_seek_to_first(table.clone())?);
+            self.next_sst_idx += 1;
+        }
+        Ok(())
+    }
+}
+
+impl StorageIterator for SstConcatIterator {
+    fn key(&self) -> &[u8] {
+        self.current.as_ref().unwrap().key()
+    }
+
+    fn value(&self) -> &[u8] {
+        self.current.as_ref().unwrap().value()
+    }
+
+    fn key_bytes(&self) -> Bytes {
+        self.current.as_ref().unwrap().key_bytes()
+    }
+
+    fn value_bytes(&self) -> Bytes {
+        self.current.as_ref().unwrap().value_bytes()
+    }
+
+    fn is_valid(&self) -> bool {
+        self.current.as_ref().is_some_and(|iter| iter.is_valid())
+    }
+
+    fn next(&mut self) -> Result<()> {
+        self.current.as_mut().unwrap().next()?;
+        self.move_until_valid()
+    }
+}
+
+impl SeekableIterator for SstConcatIterator {
+    fn seek_to_key(&mut self, key: &[u8]) -> Result<()> {
+        SstConcatIterator::seek_to_key(self, key)
+    }
+}
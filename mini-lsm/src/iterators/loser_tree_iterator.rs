@@ -0,0 +1,145 @@
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use anyhow::Result;
+use bytes::Bytes;
+
+use super::{SeekableIterator, StorageIterator};
+use crate::comparator::{self, Comparator};
+
+/// Merge multiple iterators of the same type with a loser tree. If the same key occurs multiple
+/// times in some iterators, prefer the one with smaller index. It is a drop-in alternative to
+/// `MergeIterator`, which moves the winner with `log(n)` comparisons and no swaps of iterators.
+///
+/// The tree is laid out like a binary heap: iterator `i` is the leaf at node `n + i`, each internal
+/// node `1..n` holds the loser of the match played there, and node 0 holds the overall winner.
+pub struct LoserTreeIterator<I: StorageIterator> {
+    iters: Vec<Box<I>>,
+    tree: Vec<usize>,
+    /// The key of the previous winner, reused across calls to `next`.
+    prev_key: Vec<u8>,
+    comparator: Arc<dyn Comparator>,
+}
+
+impl<I: StorageIterator> LoserTreeIterator<I> {
+    pub fn create(iters: Vec<Box<I>>) -> Self {
+        Self::create_with_comparator(iters, comparator::bytewise())
+    }
+
+    /// Merge iterators whose keys are sorted by `comparator`.
+    pub fn create_with_comparator(iters: Vec<Box<I>>, comparator: Arc<dyn Comparator>) -> Self {
+        let mut iter = Self {
+            tree: vec![0; iters.len().max(1)],
+            iters,
+            prev_key: Vec::new(),
+            comparator,
+        };
+        iter.build();
+        iter
+    }
+
+    /// Check if the iterator `a` wins the match against `b`. Invalid iterators lose to valid ones,
+    /// and ties are broken by the index.
+    fn beats(&self, a: usize, b: usize) -> bool {
+        let (iter_a, iter_b) = (&self.iters[a], &self.iters[b]);
+        match (iter_a.is_valid(), iter_b.is_valid()) {
+            (true, true) => match self.comparator.compare(iter_a.key(), iter_b.key()) {
+                Ordering::Less => true,
+                Ordering::Greater => false,
+                Ordering::Equal => a < b,
+            },
+            (true, false) => true,
+            (false, true) => false,
+            (false, false) => a < b,
+        }
+    }
+
+    /// Play all matches from the leaves.
+    fn build(&mut self) {
+        let n = self.iters.len();
+        if n == 0 {
+            return;
+        }
+        // The winner of the subtree of each node, where the leaves are at `n..2n`.
+        let mut winners = vec![0; 2 * n];
+        for (idx, winner) in winners[n..].iter_mut().enumerate() {
+            *winner = idx;
+        }
+        for node in (1..n).rev() {
+            let (left, right) = (winners[2 * node], winners[2 * node + 1]);
+            let (winner, loser) = if self.beats(left, right) {
+                (left, right)
+            } else {
+                (right, left)
+            };
+            winners[node] = winner;
+            self.tree[node] = loser;
+        }
+        self.tree[0] = if n == 1 { 0 } else { winners[1] };
+    }
+
+    /// Replay the matches from the leaf of iterator `idx` to the root after it moved.
+    fn replay(&mut self, idx: usize) {
+        let mut winner = idx;
+        let mut node = (self.iters.len() + idx) / 2;
+        while node > 0 {
+            if self.beats(self.tree[node], winner) {
+                std::mem::swap(&mut self.tree[node], &mut winner);
+            }
+            node /= 2;
+        }
+        self.tree[0] = winner;
+    }
+
+    fn current(&self) -> &I {
+        &self.iters[self.tree[0]]
+    }
+}
+
+impl<I: StorageIterator> StorageIterator for LoserTreeIterator<I> {
+    fn key(&self) -> &[u8] {
+        self.current().key()
+    }
+
+    fn value(&self) -> &[u8] {
+        self.current().value()
+    }
+
+    fn key_bytes(&self) -> Bytes {
+        self.current().key_bytes()
+    }
+
+    fn value_bytes(&self) -> Bytes {
+        self.current().value_bytes()
+    }
+
+    fn is_valid(&self) -> bool {
+        !self.iters.is_empty() && self.current().is_valid()
+    }
+
+    fn next(&mut self) -> Result<()> {
+        let winner = self.tree[0];
+        self.prev_key.clear();
+        self.prev_key.extend_from_slice(self.iters[winner].key());
+        self.iters[winner].next()?;
+        self.replay(winner);
+        // Skip the same key in the other iterators, which win next in the order of their index.
+        while self.is_valid() && self.key() == self.prev_key {
+            let winner = self.tree[0];
+            self.iters[winner].next()?;
+            self.replay(winner);
+        }
+        Ok(())
+    }
+}
+
+impl<I: SeekableIterator> SeekableIterator for LoserTreeIterator<I> {
+    /// Seek all iterators and play all matches again.
+    fn seek_to_key(&mut self, key: &[u8]) -> Result<()> {
+        for iter in self.iters.iter_mut() {
+            iter.seek_to_key(key)?;
+        }
+        self.build();
+        Ok(())
+    }
+}
@@ -1,12 +1,15 @@
 use std::cmp::{self};
 use std::collections::binary_heap::PeekMut;
 use std::collections::BinaryHeap;
+use std::sync::Arc;
 
 use anyhow::Result;
+use bytes::Bytes;
 
-use super::StorageIterator;
+use super::{SeekableIterator, StorageIterator};
+use crate::comparator::{self, Comparator};
 
-struct HeapWrapper<I: StorageIterator>(pub usize, pub Box<I>);
+struct HeapWrapper<I: StorageIterator>(pub usize, pub Box<I>, Arc<dyn Comparator>);
 
 impl<I: StorageIterator> PartialEq for HeapWrapper<I> {
     fn eq(&self, other: &Self) -> bool {
@@ -18,18 +21,16 @@ impl<I: StorageIterator> Eq for HeapWrapper<I> {}
 
 impl<I: StorageIterator> PartialOrd for HeapWrapper<I> {
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
-        match self.1.key().cmp(other.1.key()) {
-            cmp::Ordering::Greater => Some(cmp::Ordering::Greater),
-            cmp::Ordering::Less => Some(cmp::Ordering::Less),
-            cmp::Ordering::Equal => self.0.partial_cmp(&other.0),
-        }
-        .map(|x| x.reverse())
+        Some(self.cmp(other))
     }
 }
 
 impl<I: StorageIterator> Ord for HeapWrapper<I> {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+        self.2
+            .compare(self.1.key(), other.1.key())
+            .then(self.0.cmp(&other.0))
+            .reverse()
     }
 }
 
@@ -38,38 +39,35 @@ impl<I: StorageIterator> Ord for HeapWrapper<I> {
 pub struct MergeIterator<I: StorageIterator> {
     iters: BinaryHeap<HeapWrapper<I>>,
     current: Option<HeapWrapper<I>>,
+    /// The iterators that are no longer valid, which are kept so that they can be seeked again.
+    exhausted: Vec<HeapWrapper<I>>,
+    comparator: Arc<dyn Comparator>,
 }
 
 impl<I: StorageIterator> MergeIterator<I> {
     pub fn create(iters: Vec<Box<I>>) -> Self {
-        if iters.is_empty() {
-            return Self {
-                iters: BinaryHeap::new(),
-                current: None,
-            };
-        }
+        Self::create_with_comparator(iters, comparator::bytewise())
+    }
 
+    /// Merge iterators whose keys are sorted by `comparator`.
+    pub fn create_with_comparator(iters: Vec<Box<I>>, comparator: Arc<dyn Comparator>) -> Self {
         let mut heap = BinaryHeap::new();
-
-        if iters.iter().all(|x| !x.is_valid()) {
-            // All invalid, select the last one as the current.
-            let mut iters = iters;
-            return Self {
-                iters: heap,
-                current: Some(HeapWrapper(0, iters.pop().unwrap())),
-            };
-        }
-
+        let mut exhausted = Vec::new();
         for (idx, iter) in iters.into_iter().enumerate() {
-            if iter.is_valid() {
-                heap.push(HeapWrapper(idx, iter));
+            let iter = HeapWrapper(idx, iter, comparator.clone());
+            if iter.1.is_valid() {
+                heap.push(iter);
+            } else {
+                exhausted.push(iter);
             }
         }
 
-        let current = heap.pop().unwrap();
+        let current = heap.pop();
         Self {
             iters: heap,
-            current: Some(current),
+            current,
+            exhausted,
+            comparator,
         }
     }
 }
@@ -85,6 +83,18 @@ impl<I: StorageIterator> StorageIterator for MergeIterator<I> {
             .value()
     }
 
+    fn value_bytes(&self) -> Bytes {
+        unsafe { self.current.as_ref().unwrap_unchecked() }
+            .1
+            .value_bytes()
+    }
+
+    fn key_bytes(&self) -> Bytes {
+        unsafe { self.current.as_ref().unwrap_unchecked() }
+            .1
+            .key_bytes()
+    }
+
     fn is_valid(&self) -> bool {
         self.current
             .as_ref()
@@ -97,19 +107,21 @@ impl<I: StorageIterator> StorageIterator for MergeIterator<I> {
         // Pop the item out of the heap if they have the same value.
         while let Some(mut inner_iter) = self.iters.peek_mut() {
             debug_assert!(
-                inner_iter.1.key() >= current.1.key(),
+                self.comparator
+                    .compare(inner_iter.1.key(), current.1.key())
+                    .is_ge(),
                 "heap invariant violated"
             );
             if inner_iter.1.key() == current.1.key() {
                 // Case 1: an error occurred when calling `next`.
                 if let e @ Err(_) = inner_iter.1.next() {
-                    PeekMut::pop(inner_iter);
+                    self.exhausted.push(PeekMut::pop(inner_iter));
                     return e;
                 }
 
                 // Case 2: iter is no longer valid.
                 if !inner_iter.1.is_valid() {
-                    PeekMut::pop(inner_iter);
+                    self.exhausted.push(PeekMut::pop(inner_iter));
                 }
             } else {
                 break;
@@ -121,7 +133,7 @@ impl<I: StorageIterator> StorageIterator for MergeIterator<I> {
         // If the current iterator is invalid, pop it out of the heap and select the next one.
         if !current.1.is_valid() {
             if let Some(iter) = self.iters.pop() {
-                *current = iter;
+                self.exhausted.push(std::mem::replace(current, iter));
             }
             return Ok(());
         }
@@ -136,3 +148,29 @@ impl<I: StorageIterator> StorageIterator for MergeIterator<I> {
         Ok(())
     }
 }
+
+impl<I: SeekableIterator> SeekableIterator for MergeIterator<I> {
+    /// Seek all iterators, including the exhausted ones, and rebuild the heap. If any of them
+    /// fails, the merge iterator becomes invalid.
+    fn seek_to_key(&mut self, key: &[u8]) -> Result<()> {
+        let mut iters = std::mem::take(&mut self.iters).into_vec();
+        iters.append(&mut self.exhausted);
+        iters.extend(self.current.take());
+        let mut result = Ok(());
+        for mut iter in iters {
+            if let e @ Err(_) = iter.1.seek_to_key(key) {
+                result = e;
+            }
+            if result.is_ok() && iter.1.is_valid() {
+                self.iters.push(iter);
+            } else {
+                self.exhausted.push(iter);
+            }
+        }
+        if result.is_err() {
+            self.exhausted.extend(std::mem::take(&mut self.iters));
+        }
+        self.current = self.iters.pop();
+        result
+    }
+}
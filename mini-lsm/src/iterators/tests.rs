@@ -1,8 +1,10 @@
 use anyhow::Result;
 use bytes::Bytes;
 
-use super::StorageIterator;
+use super::{SeekableIterator, StorageIterator};
 
+pub mod concat_iterator_test;
+pub mod loser_tree_iterator_test;
 pub mod merge_iterator_test;
 pub mod two_merge_iterator_test;
 
@@ -38,3 +40,10 @@ impl StorageIterator for MockIterator {
         self.index < self.data.len()
     }
 }
+
+impl SeekableIterator for MockIterator {
+    fn seek_to_key(&mut self, key: &[u8]) -> Result<()> {
+        self.index = self.data.partition_point(|(k, _)| &k[..] < key);
+        Ok(())
+    }
+}
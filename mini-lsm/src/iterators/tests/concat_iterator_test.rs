@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use tempfile::{tempdir, TempDir};
+
+use super::*;
+use crate::iterators::concat_iterator::SstConcatIterator;
+use crate::iterators::merge_iterator::MergeIterator;
+use crate::iterators::two_merge_iterator::TwoMergeIterator;
+use crate::table::{SsTable, SsTableBuilder};
+
+fn key_of(idx: usize) -> Bytes {
+    Bytes::from(format!("key_{:03}", idx))
+}
+
+fn value_of(idx: usize) -> Bytes {
+    Bytes::from(format!("value_{:03}", idx))
+}
+
+/// Build `num_of_tables` non-overlapping SSTs of 10 consecutive keys each.
+fn generate_ssts(num_of_tables: usize) -> (TempDir, Vec<Arc<SsTable>>) {
+    let dir = tempdir().unwrap();
+    let tables = (0..num_of_tables)
+        .map(|table_idx| {
+            let mut builder = SsTableBuilder::new(64);
+            for idx in table_idx * 10..(table_idx + 1) * 10 {
+                builder.add(&key_of(idx), &value_of(idx));
+            }
+            let path = dir.path().join(format!("{}.sst", table_idx));
+            Arc::new(builder.build(table_idx, None, path).unwrap())
+        })
+        .collect();
+    (dir, tables)
+}
+
+fn check_iter_result(mut iter: impl StorageIterator, range: impl Iterator<Item = usize>) {
+    for idx in range {
+        assert!(iter.is_valid());
+        assert_eq!(iter.key(), key_of(idx));
+        assert_eq!(iter.value(), value_of(idx));
+        iter.next().unwrap();
+    }
+    assert!(!iter.is_valid());
+}
+
+#[test]
+fn test_concat_iterator() {
+    let (_dir, tables) = generate_ssts(5);
+    let iter = SstConcatIterator::create_and_seek_to_first(tables.clone()).unwrap();
+    check_iter_result(iter, 0..50);
+    for idx in 0..50 {
+        let iter = SstConcatIterator::create_and_seek_to_key(tables.clone(), &key_of(idx)).unwrap();
+        check_iter_result(iter, idx..50);
+    }
+    let iter = SstConcatIterator::create_and_seek_to_key(tables.clone(), b"a").unwrap();
+    check_iter_result(iter, 0..50);
+    let iter = SstConcatIterator::create_and_seek_to_key(tables.clone(), b"key_019_").unwrap();
+    check_iter_result(iter, 20..50);
+    let mut iter = SstConcatIterator::create_and_seek_to_key(tables.clone(), b"z").unwrap();
+    assert!(!iter.is_valid());
+    iter.seek_to_first().unwrap();
+    check_iter_result(iter, 0..50);
+    let iter = SstConcatIterator::create_and_seek_to_first(vec![]).unwrap();
+    assert!(!iter.is_valid());
+}
+
+#[test]
+fn test_concat_iterator_merge() {
+    let (_dir, tables) = generate_ssts(4);
+    let iter = MergeIterator::create(vec![
+        Box::new(
+            SstConcatIterator::create_and_seek_to_first(vec![tables[0].clone(), tables[2].clone()])
+                .unwrap(),
+        ),
+        Box::new(
+            SstConcatIterator::create_and_seek_to_first(vec![tables[1].clone(), tables[3].clone()])
+                .unwrap(),
+        ),
+    ]);
+    check_iter_result(iter, 0..40);
+    let iter = TwoMergeIterator::create(
+        SstConcatIterator::create_and_seek_to_first(vec![tables[1].clone()]).unwrap(),
+        SstConcatIterator::create_and_seek_to_first(vec![tables[0].clone(), tables[2].clone()])
+            .unwrap(),
+    )
+    .unwrap();
+    check_iter_result(iter, 0..30);
+}
@@ -0,0 +1,87 @@
+use super::*;
+use crate::iterators::loser_tree_iterator::LoserTreeIterator;
+use crate::iterators::merge_iterator::MergeIterator;
+use crate::iterators::SeekableIterator;
+
+fn collect(mut iter: impl StorageIterator) -> Vec<(Bytes, Bytes)> {
+    let mut result = Vec::new();
+    while iter.is_valid() {
+        result.push((
+            Bytes::copy_from_slice(iter.key()),
+            Bytes::copy_from_slice(iter.value()),
+        ));
+        iter.next().unwrap();
+    }
+    result
+}
+
+/// Generate `num_of_iters` iterators with overlapping keys, where the value records which
+/// iterator the entry comes from.
+fn generate_iters(num_of_iters: usize) -> Vec<MockIterator> {
+    let mut seed = 0x2333u64;
+    (0..num_of_iters)
+        .map(|iter_idx| {
+            let mut keys: Vec<u64> = (0..20)
+                .map(|_| {
+                    seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                    (seed >> 33) % 50
+                })
+                .collect();
+            keys.sort();
+            keys.dedup();
+            MockIterator::new(
+                keys.into_iter()
+                    .map(|key| {
+                        (
+                            Bytes::from(format!("{:02}", key)),
+                            Bytes::from(format!("{}.{}", key, iter_idx)),
+                        )
+                    })
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_loser_tree_same_as_heap() {
+    for num_of_iters in 0..10 {
+        let iters = generate_iters(num_of_iters);
+        let expected = collect(MergeIterator::create(
+            iters.iter().cloned().map(Box::new).collect(),
+        ));
+        let actual = collect(LoserTreeIterator::create(
+            iters.into_iter().map(Box::new).collect(),
+        ));
+        assert_eq!(actual, expected);
+    }
+}
+
+#[test]
+fn test_loser_tree_prefer_smaller_index() {
+    let i1 = MockIterator::new(vec![
+        (Bytes::from("a"), Bytes::from("1.1")),
+        (Bytes::from("b"), Bytes::from("2.1")),
+    ]);
+    let i2 = MockIterator::new(vec![
+        (Bytes::from("a"), Bytes::from("1.2")),
+        (Bytes::from("c"), Bytes::from("3.2")),
+    ]);
+    let i3 = MockIterator::new(vec![]);
+    let i4 = MockIterator::new(vec![
+        (Bytes::from("a"), Bytes::from("1.4")),
+        (Bytes::from("b"), Bytes::from("2.4")),
+        (Bytes::from("c"), Bytes::from("3.4")),
+    ]);
+    let mut iter =
+        LoserTreeIterator::create(vec![Box::new(i1), Box::new(i2), Box::new(i3), Box::new(i4)]);
+    let expected = vec![
+        (Bytes::from("a"), Bytes::from("1.1")),
+        (Bytes::from("b"), Bytes::from("2.1")),
+        (Bytes::from("c"), Bytes::from("3.2")),
+    ];
+    iter.seek_to_key(b"b").unwrap();
+    assert_eq!(iter.value(), b"2.1");
+    iter.seek_to_key(b"a").unwrap();
+    assert_eq!(collect(iter), expected);
+}
@@ -1,5 +1,6 @@
 use super::*;
 use crate::iterators::merge_iterator::MergeIterator;
+use crate::iterators::SeekableIterator;
 
 fn as_bytes(x: &[u8]) -> Bytes {
     Bytes::copy_from_slice(x)
@@ -135,3 +136,38 @@ fn test_merge_empty() {
     let iter = MergeIterator::<MockIterator>::create(vec![]);
     check_iter_result(iter, vec![]);
 }
+
+#[test]
+fn test_merge_seek() {
+    let i1 = MockIterator::new(vec![
+        (Bytes::from("a"), Bytes::from("1.1")),
+        (Bytes::from("c"), Bytes::from("3.1")),
+        (Bytes::from("e"), Bytes::from("5.1")),
+    ]);
+    let i2 = MockIterator::new(vec![
+        (Bytes::from("b"), Bytes::from("2.2")),
+        (Bytes::from("c"), Bytes::from("3.2")),
+    ]);
+    let mut iter = MergeIterator::create(vec![Box::new(i1), Box::new(i2)]);
+    // Exhaust the iterators, and seek back.
+    while iter.is_valid() {
+        iter.next().unwrap();
+    }
+    iter.seek_to_key(b"b").unwrap();
+    assert_eq!(iter.key(), b"b");
+    iter.next().unwrap();
+    assert_eq!(iter.value(), b"3.1");
+    iter.seek_to_key(b"a").unwrap();
+    check_iter_result(
+        iter,
+        vec![
+            (Bytes::from("a"), Bytes::from("1.1")),
+            (Bytes::from("b"), Bytes::from("2.2")),
+            (Bytes::from("c"), Bytes::from("3.1")),
+            (Bytes::from("e"), Bytes::from("5.1")),
+        ],
+    );
+    let mut iter = MergeIterator::create(vec![Box::new(MockIterator::new(vec![]))]);
+    iter.seek_to_key(b"a").unwrap();
+    assert!(!iter.is_valid());
+}
@@ -1,6 +1,10 @@
+use std::sync::Arc;
+
 use anyhow::Result;
+use bytes::Bytes;
 
-use super::StorageIterator;
+use super::{SeekableIterator, StorageIterator};
+use crate::comparator::{self, Comparator};
 
 /// Merges two iterators of different types into one. If the two iterators have the same key, only
 /// produce the key once and prefer the entry from A.
@@ -8,17 +12,18 @@ pub struct TwoMergeIterator<A: StorageIterator, B: StorageIterator> {
     a: A,
     b: B,
     choose_a: bool,
+    comparator: Arc<dyn Comparator>,
 }
 
 impl<A: StorageIterator, B: StorageIterator> TwoMergeIterator<A, B> {
-    fn choose_a(a: &A, b: &B) -> bool {
+    fn choose_a(a: &A, b: &B, comparator: &dyn Comparator) -> bool {
         if !a.is_valid() {
             return false;
         }
         if !b.is_valid() {
             return true;
         }
-        a.key() < b.key()
+        comparator.compare(a.key(), b.key()).is_lt()
     }
 
     fn skip_b(&mut self) -> Result<()> {
@@ -31,13 +36,19 @@ impl<A: StorageIterator, B: StorageIterator> TwoMergeIterator<A, B> {
     }
 
     pub fn create(a: A, b: B) -> Result<Self> {
+        Self::create_with_comparator(a, b, comparator::bytewise())
+    }
+
+    /// Merge two iterators whose keys are sorted by `comparator`.
+    pub fn create_with_comparator(a: A, b: B, comparator: Arc<dyn Comparator>) -> Result<Self> {
         let mut iter = Self {
             choose_a: false,
             a,
             b,
+            comparator,
         };
         iter.skip_b()?;
-        iter.choose_a = Self::choose_a(&iter.a, &iter.b);
+        iter.choose_a = Self::choose_a(&iter.a, &iter.b, iter.comparator.as_ref());
         Ok(iter)
     }
 }
@@ -59,6 +70,22 @@ impl<A: StorageIterator, B: StorageIterator> StorageIterator for TwoMergeIterato
         }
     }
 
+    fn key_bytes(&self) -> Bytes {
+        if self.choose_a {
+            self.a.key_bytes()
+        } else {
+            self.b.key_bytes()
+        }
+    }
+
+    fn value_bytes(&self) -> Bytes {
+        if self.choose_a {
+            self.a.value_bytes()
+        } else {
+            self.b.value_bytes()
+        }
+    }
+
     fn is_valid(&self) -> bool {
         if self.choose_a {
             self.a.is_valid()
@@ -74,7 +101,17 @@ impl<A: StorageIterator, B: StorageIterator> StorageIterator for TwoMergeIterato
             self.b.next()?;
         }
         self.skip_b()?;
-        self.choose_a = Self::choose_a(&self.a, &self.b);
+        self.choose_a = Self::choose_a(&self.a, &self.b, self.comparator.as_ref());
+        Ok(())
+    }
+}
+
+impl<A: SeekableIterator, B: SeekableIterator> SeekableIterator for TwoMergeIterator<A, B> {
+    fn seek_to_key(&mut self, key: &[u8]) -> Result<()> {
+        self.a.seek_to_key(key)?;
+        self.b.seek_to_key(key)?;
+        self.skip_b()?;
+        self.choose_a = Self::choose_a(&self.a, &self.b, self.comparator.as_ref());
         Ok(())
     }
 }
@@ -1,8 +1,14 @@
 pub mod block;
+pub mod comparator;
+pub mod durable;
+pub mod hash;
 pub mod iterators;
 pub mod lsm_iterator;
 pub mod lsm_storage;
 pub mod mem_table;
+pub mod prefix;
+pub mod rate_limiter;
+pub mod scan;
 pub mod table;
 
 #[cfg(test)]
@@ -1,45 +1,85 @@
 use std::ops::Bound;
+use std::sync::Arc;
 
 use anyhow::Result;
 use bytes::Bytes;
 
+use crate::comparator::Comparator;
+use crate::iterators::concat_iterator::SstConcatIterator;
 use crate::iterators::merge_iterator::MergeIterator;
 use crate::iterators::two_merge_iterator::TwoMergeIterator;
-use crate::iterators::StorageIterator;
+use crate::iterators::{SeekableIterator, StorageIterator};
 use crate::mem_table::MemTableIterator;
 use crate::table::SsTableIterator;
 
-type LsmIteratorInner =
-    TwoMergeIterator<MergeIterator<MemTableIterator>, MergeIterator<SsTableIterator>>;
+pub(crate) type LsmIteratorInner = TwoMergeIterator<
+    TwoMergeIterator<MergeIterator<MemTableIterator>, MergeIterator<SsTableIterator>>,
+    MergeIterator<SstConcatIterator>,
+>;
+
+/// Creates the inner iterator from the latest snapshot of the storage, starting from the given
+/// lower bound.
+pub(crate) type LsmIteratorInnerBuilder =
+    Box<dyn Fn(Bound<&[u8]>) -> Result<LsmIteratorInner> + Send + Sync>;
 
 pub struct LsmIterator {
     iter: LsmIteratorInner,
+    start_bound: Bound<Bytes>,
     end_bound: Bound<Bytes>,
     is_valid: bool,
+    comparator: Arc<dyn Comparator>,
+    builder: LsmIteratorInnerBuilder,
 }
 
 impl LsmIterator {
-    pub(crate) fn new(iter: LsmIteratorInner, end_bound: Bound<Bytes>) -> Result<Self> {
+    pub(crate) fn new(
+        builder: LsmIteratorInnerBuilder,
+        start_bound: Bound<&[u8]>,
+        end_bound: Bound<Bytes>,
+        comparator: Arc<dyn Comparator>,
+    ) -> Result<Self> {
         let mut iter = Self {
-            is_valid: iter.is_valid(),
-            iter,
+            is_valid: false,
+            iter: builder(start_bound)?,
+            start_bound: start_bound.map(Bytes::copy_from_slice),
             end_bound,
+            comparator,
+            builder,
         };
+        iter.update_is_valid();
         iter.move_to_non_delete()?;
         Ok(iter)
     }
 
-    fn next_inner(&mut self) -> Result<()> {
-        self.iter.next()?;
-        if !self.iter.is_valid() {
-            self.is_valid = false;
+    /// Pick up the latest snapshot of the storage, staying at the current key, or moving to the
+    /// next key if the current key is deleted in the snapshot. An exhausted iterator stays
+    /// exhausted.
+    pub fn refresh(&mut self) -> Result<()> {
+        if !self.is_valid {
             return Ok(());
         }
-        match self.end_bound.as_ref() {
-            Bound::Unbounded => {}
-            Bound::Included(key) => self.is_valid = self.iter.key() <= key.as_ref(),
-            Bound::Excluded(key) => self.is_valid = self.iter.key() < key.as_ref(),
+        let key = Bytes::copy_from_slice(self.iter.key());
+        self.iter = (self.builder)(Bound::Included(&key))?;
+        self.update_is_valid();
+        self.move_to_non_delete()
+    }
+
+    /// The iterator is valid if the inner iterator is valid and within the end bound.
+    fn update_is_valid(&mut self) {
+        if !self.iter.is_valid() {
+            self.is_valid = false;
+            return;
         }
+        self.is_valid = match self.end_bound.as_ref() {
+            Bound::Unbounded => true,
+            Bound::Included(key) => self.comparator.compare(self.iter.key(), key).is_le(),
+            Bound::Excluded(key) => self.comparator.compare(self.iter.key(), key).is_lt(),
+        };
+    }
+
+    fn next_inner(&mut self) -> Result<()> {
+        self.iter.next()?;
+        self.update_is_valid();
         Ok(())
     }
 
@@ -64,6 +104,14 @@ impl StorageIterator for LsmIterator {
         self.iter.value()
     }
 
+    fn key_bytes(&self) -> Bytes {
+        self.iter.key_bytes()
+    }
+
+    fn value_bytes(&self) -> Bytes {
+        self.iter.value_bytes()
+    }
+
     fn next(&mut self) -> Result<()> {
         self.next_inner()?;
         self.move_to_non_delete()?;
@@ -71,6 +119,29 @@ impl StorageIterator for LsmIterator {
     }
 }
 
+impl SeekableIterator for LsmIterator {
+    /// Seek to the first key that is >= `key`, within the bounds of the scan. The merge iterators
+    /// are seeked in place, keeping the snapshot of the scan. A key below the lower bound seeks
+    /// to the lower bound, as the scan left out the SSTs outside its range.
+    fn seek_to_key(&mut self, key: &[u8]) -> Result<()> {
+        match self.start_bound.clone() {
+            Bound::Included(lower) if self.comparator.compare(key, &lower).is_lt() => {
+                self.iter.seek_to_key(&lower)?;
+            }
+            Bound::Excluded(lower) if self.comparator.compare(key, &lower).is_le() => {
+                self.iter.seek_to_key(&lower)?;
+                if self.iter.is_valid() && self.comparator.compare(self.iter.key(), &lower).is_eq()
+                {
+                    self.iter.next()?;
+                }
+            }
+            _ => self.iter.seek_to_key(key)?,
+        }
+        self.update_is_valid();
+        self.move_to_non_delete()
+    }
+}
+
 /// A wrapper around existing iterator, will prevent users from calling `next` when the iterator is
 /// invalid.
 pub struct FusedIterator<I: StorageIterator> {
@@ -96,6 +167,14 @@ impl<I: StorageIterator> StorageIterator for FusedIterator<I> {
         self.iter.value()
     }
 
+    fn key_bytes(&self) -> Bytes {
+        self.iter.key_bytes()
+    }
+
+    fn value_bytes(&self) -> Bytes {
+        self.iter.value_bytes()
+    }
+
     fn next(&mut self) -> Result<()> {
         // only move when the iterator is valid
         if self.iter.is_valid() {
@@ -104,3 +183,63 @@ impl<I: StorageIterator> StorageIterator for FusedIterator<I> {
         Ok(())
     }
 }
+
+impl<I: SeekableIterator> SeekableIterator for FusedIterator<I> {
+    fn seek_to_key(&mut self, key: &[u8]) -> Result<()> {
+        self.iter.seek_to_key(key)
+    }
+}
+
+impl FusedIterator<LsmIterator> {
+    /// Pick up the latest snapshot of the storage. See [`LsmIterator::refresh`].
+    pub fn refresh(&mut self) -> Result<()> {
+        self.iter.refresh()
+    }
+}
+
+impl<I: StorageIterator> IntoIterator for FusedIterator<I> {
+    type Item = Result<(Bytes, Bytes)>;
+    type IntoIter = Entries<I>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Entries {
+            iter: self,
+            started: false,
+            done: false,
+        }
+    }
+}
+
+/// Adapts a storage iterator to a [`std::iter::Iterator`] over its key-value pairs. The iterator
+/// only moves forward when the next entry is requested, and it ends after yielding an error.
+pub struct Entries<I: StorageIterator> {
+    iter: FusedIterator<I>,
+    /// Whether the first entry has been yielded, after which the iterator must move forward
+    /// before yielding the next one.
+    started: bool,
+    done: bool,
+}
+
+impl<I: StorageIterator> Iterator for Entries<I> {
+    type Item = Result<(Bytes, Bytes)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if self.started {
+            if let Err(e) = self.iter.next() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+        self.started = true;
+        if !self.iter.is_valid() {
+            self.done = true;
+            return None;
+        }
+        Some(Ok((self.iter.key_bytes(), self.iter.value_bytes())))
+    }
+}
+
+impl<I: StorageIterator> std::iter::FusedIterator for Entries<I> {}
@@ -1,20 +1,124 @@
-use std::ops::Bound;
+mod compact;
+mod write_stall;
+
+use std::fs::{File, TryLockError};
+use std::ops::{Bound, RangeBounds};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use bytes::Bytes;
-use parking_lot::{Mutex, RwLock};
+use parking_lot::{Condvar, Mutex, MutexGuard, RwLock};
+pub use write_stall::{WriteStallOptions, WriteStallState, WriteStallStats};
 
 use crate::block::Block;
+use crate::comparator::{self, Comparator};
+use crate::durable;
+use crate::hash::key_hash;
+use crate::iterators::concat_iterator::SstConcatIterator;
 use crate::iterators::merge_iterator::MergeIterator;
 use crate::iterators::two_merge_iterator::TwoMergeIterator;
 use crate::iterators::StorageIterator;
-use crate::lsm_iterator::{FusedIterator, LsmIterator};
+use crate::lsm_iterator::{FusedIterator, LsmIterator, LsmIteratorInner};
 use crate::mem_table::{map_bound, MemTable};
-use crate::table::{SsTable, SsTableBuilder, SsTableIterator};
+use crate::prefix::{prefix_upper_bound, PrefixExtractor};
+use crate::rate_limiter::{IoPriority, RateLimiter};
+use crate::scan::{ContinuationToken, ScanOptions, ScanPage};
+use crate::table::{
+    CompressionType, SsTable, SsTableBuilder, SsTableIterator, TableCreationReason,
+};
+
+/// The key of a block in the block cache, by the ID of its SST.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlockCacheKey {
+    /// A data block, by its index.
+    Data(usize, usize),
+    /// A partition of a partitioned index, by its index.
+    IndexPartition(usize, usize),
+}
+
+pub type BlockCache = moka::sync::Cache<BlockCacheKey, Arc<Block>>;
+
+/// The file holding the name of the comparator of the storage.
+const COMPARATOR_FILE: &str = "COMPARATOR";
+
+/// The file locked by the storage while it is open, so that no other storage opens the directory.
+const LOCK_FILE: &str = "LOCK";
+
+/// Number of locks that writes to keys are striped over.
+const KEY_LOCK_STRIPES: usize = 64;
 
-pub type BlockCache = moka::sync::Cache<(usize, usize), Arc<Block>>;
+/// Number of levels below L0, where the last one is the bottom level.
+pub const NUM_LEVELS: usize = 6;
+
+/// Options of the storage engine.
+#[derive(Clone, Debug)]
+pub struct LsmStorageOptions {
+    /// The target size of data blocks in SSTs.
+    pub block_size: usize,
+    /// The compression codec of SSTs on each level, starting from L0. Levels beyond the end use
+    /// the last codec, and no compression is used if it is empty.
+    pub compression_per_level: Vec<CompressionType>,
+    /// If set, partition the index of SSTs into blocks of this size, which are loaded through the
+    /// block cache on demand instead of keeping the whole index in memory.
+    pub index_partition_size: Option<usize>,
+    /// Build a hash index in every data block for faster point lookups.
+    pub block_hash_index: bool,
+    /// If set, build a bloom filter over the key prefixes in every SST, which lets `get` and
+    /// `prefix_scan` skip SSTs without the prefix.
+    pub prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+    /// Bits per prefix of the prefix bloom filters.
+    pub prefix_bloom_bits_per_key: usize,
+    /// If set, build a range filter in every SST, which lets `scan` skip SSTs without keys in
+    /// the range. The filter keeps this many more bytes of each key beyond the shortest prefix
+    /// that tells it apart from its neighbours, trading memory for fewer false positives.
+    pub range_filter_suffix_bytes: Option<usize>,
+    /// The order of keys. It is persisted when the storage is created, and the storage refuses to
+    /// open with another comparator. Range filters and prefix scans need the bytewise order.
+    pub comparator: Arc<dyn Comparator>,
+    /// The target size of SSTs written by compactions, which start a new SST once the current one
+    /// reaches this size.
+    pub target_sst_size: usize,
+    /// The maximum number of threads a compaction is split over, each of which merges its own key
+    /// range of the inputs.
+    pub max_subcompactions: usize,
+    /// When writes are delayed or stopped because flushing and compaction fall behind.
+    pub write_stall: WriteStallOptions,
+    /// If set, SSTs are written through the rate limiter, where flushes have priority over
+    /// compactions. The limiter may be shared with other storages.
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl Default for LsmStorageOptions {
+    fn default() -> Self {
+        Self {
+            block_size: 4096,
+            compression_per_level: vec![],
+            index_partition_size: None,
+            block_hash_index: false,
+            prefix_extractor: None,
+            prefix_bloom_bits_per_key: 10,
+            range_filter_suffix_bytes: None,
+            comparator: comparator::bytewise(),
+            target_sst_size: 2 << 20,
+            max_subcompactions: 1,
+            write_stall: WriteStallOptions::default(),
+            rate_limiter: None,
+        }
+    }
+}
+
+impl LsmStorageOptions {
+    /// Get the compression codec of the given level.
+    pub fn compression_of_level(&self, level: usize) -> CompressionType {
+        self.compression_per_level
+            .get(level)
+            .or(self.compression_per_level.last())
+            .copied()
+            .unwrap_or_default()
+    }
+}
 
 #[derive(Clone)]
 pub struct LsmStorageInner {
@@ -25,20 +129,16 @@ pub struct LsmStorageInner {
     /// L0 SsTables, from earliest to latest.
     l0_sstables: Vec<Arc<SsTable>>,
     /// L1 - L6 SsTables, sorted by key range.
-    #[allow(dead_code)]
     levels: Vec<Vec<Arc<SsTable>>>,
-    /// The next SSTable ID.
-    next_sst_id: usize,
 }
 
 impl LsmStorageInner {
-    fn create() -> Self {
+    fn create(comparator: Arc<dyn Comparator>) -> Self {
         Self {
-            memtable: Arc::new(MemTable::create()),
+            memtable: Arc::new(MemTable::create_with_comparator(comparator)),
             imm_memtables: vec![],
             l0_sstables: vec![],
-            levels: vec![],
-            next_sst_id: 1,
+            levels: vec![vec![]; NUM_LEVELS],
         }
     }
 }
@@ -47,20 +147,89 @@ impl LsmStorageInner {
 pub struct LsmStorage {
     inner: Arc<RwLock<Arc<LsmStorageInner>>>,
     flush_lock: Mutex<()>,
+    /// Held by manual compactions, so that their inputs are not compacted twice.
+    compaction_lock: Mutex<()>,
+    /// The next SSTable ID.
+    next_sst_id: AtomicUsize,
+    /// Stopped writes wait on the condition variable until the SSTs or memtables change.
+    write_stall_lock: Mutex<()>,
+    write_stall_cond: Condvar,
+    write_stall_stats: Mutex<WriteStallStats>,
+    /// Writes hold the lock of their key, so that conditional writes are atomic against other
+    /// writes to the same key.
+    key_locks: Vec<Mutex<()>>,
     path: PathBuf,
     block_cache: Arc<BlockCache>,
+    options: LsmStorageOptions,
+    /// The locked `LOCK_FILE`, which is unlocked when the storage is dropped.
+    _lock: File,
 }
 
 impl LsmStorage {
     pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open_with_options(path, LsmStorageOptions::default())
+    }
+
+    pub fn open_with_options(path: impl AsRef<Path>, options: LsmStorageOptions) -> Result<Self> {
+        durable::create_dir_all(path.as_ref())?;
+        let lock = Self::lock_dir(path.as_ref())?;
+        Self::check_comparator(path.as_ref(), options.comparator.as_ref())?;
+        // Only this storage writes to the directory now, so the temporary files are leftovers.
+        durable::remove_temp_files(path.as_ref())?;
         Ok(Self {
-            inner: Arc::new(RwLock::new(Arc::new(LsmStorageInner::create()))),
+            inner: Arc::new(RwLock::new(Arc::new(LsmStorageInner::create(
+                options.comparator.clone(),
+            )))),
             flush_lock: Mutex::new(()),
+            compaction_lock: Mutex::new(()),
+            next_sst_id: AtomicUsize::new(1),
+            write_stall_lock: Mutex::new(()),
+            write_stall_cond: Condvar::new(),
+            write_stall_stats: Mutex::new(WriteStallStats::default()),
+            key_locks: (0..KEY_LOCK_STRIPES).map(|_| Mutex::new(())).collect(),
             path: path.as_ref().to_path_buf(),
             block_cache: Arc::new(BlockCache::new(1 << 20)), // 4GB block cache
+            options,
+            _lock: lock,
         })
     }
 
+    /// Lock the directory of the storage, failing if another storage has it open.
+    fn lock_dir(path: &Path) -> Result<File> {
+        let lock_path = path.join(LOCK_FILE);
+        let lock = File::create(&lock_path)
+            .with_context(|| format!("failed to create {}", lock_path.display()))?;
+        match lock.try_lock() {
+            Ok(()) => Ok(lock),
+            Err(TryLockError::WouldBlock) => {
+                bail!("storage at {} is already open", path.display())
+            }
+            Err(TryLockError::Error(e)) => {
+                Err(e).with_context(|| format!("failed to lock {}", lock_path.display()))
+            }
+        }
+    }
+
+    /// Persist the name of the comparator when the storage is created, or check that it is the
+    /// one the storage was created with.
+    fn check_comparator(path: &Path, comparator: &dyn Comparator) -> Result<()> {
+        let comparator_path = path.join(COMPARATOR_FILE);
+        if comparator_path.exists() {
+            let persisted = std::fs::read_to_string(&comparator_path)
+                .with_context(|| format!("failed to read {}", comparator_path.display()))?;
+            if persisted != comparator.name() {
+                bail!(
+                    "storage was created with comparator {:?}, but opened with {:?}",
+                    persisted,
+                    comparator.name()
+                );
+            }
+        } else {
+            durable::write(&comparator_path, comparator.name().as_bytes())?;
+        }
+        Ok(())
+    }
+
     /// Get a key from the storage. In day 7, this can be further optimized by using a bloom filter.
     pub fn get(&self, key: &[u8]) -> Result<Option<Bytes>> {
         let snapshot = {
@@ -86,28 +255,138 @@ impl LsmStorage {
                 return Ok(Some(value));
             }
         }
-        let mut iters = Vec::new();
-        iters.reserve(snapshot.l0_sstables.len());
+        // Search on L0 SSTs, from the latest to the earliest.
         for table in snapshot.l0_sstables.iter().rev() {
-            iters.push(Box::new(SsTableIterator::create_and_seek_to_key(
-                table.clone(),
-                key,
-            )?));
+            if !self.table_may_contain(table, key) {
+                continue;
+            }
+            if let Some(value) = table.get(key)? {
+                if value.is_empty() {
+                    // found tomestone, return key not exists
+                    return Ok(None);
+                }
+                return Ok(Some(value));
+            }
         }
-        let iter = MergeIterator::create(iters);
-        if iter.is_valid() {
-            return Ok(Some(Bytes::copy_from_slice(iter.value())));
+        // Search on the levels, where at most one SST of each level may contain the key.
+        for level in snapshot.levels.iter() {
+            let Some(table) = self.find_table_in_level(level, key) else {
+                continue;
+            };
+            if !self.table_may_contain(table, key) {
+                continue;
+            }
+            if let Some(value) = table.get(key)? {
+                if value.is_empty() {
+                    // found tomestone, return key not exists
+                    return Ok(None);
+                }
+                return Ok(Some(value));
+            }
         }
         Ok(None)
     }
 
+    /// Get many keys from the storage at once, from the same snapshot. The keys are sorted and
+    /// looked up SST by SST, so that each data block is read once. Returns the values in the
+    /// order of `keys`.
+    pub fn multi_get(&self, keys: &[&[u8]]) -> Result<Vec<Option<Bytes>>> {
+        let snapshot = {
+            let guard = self.inner.read();
+            Arc::clone(&guard)
+        }; // drop global lock here
+
+        // The values found so far, where an empty value is a tombstone.
+        let mut values: Vec<Option<Bytes>> = vec![None; keys.len()];
+        // Indices of the keys not found yet, sorted by key.
+        let mut pending: Vec<usize> = (0..keys.len()).collect();
+        let comparator = self.options.comparator.as_ref();
+        pending.sort_by(|a, b| comparator.compare(keys[*a], keys[*b]));
+
+        // Search on the current memtable and immutable memtables.
+        let memtables =
+            std::iter::once(&snapshot.memtable).chain(snapshot.imm_memtables.iter().rev());
+        for memtable in memtables {
+            pending.retain(|idx| {
+                values[*idx] = memtable.get(keys[*idx]);
+                values[*idx].is_none()
+            });
+        }
+        // Search on L0 SSTs, from the latest to the earliest.
+        for table in snapshot.l0_sstables.iter().rev() {
+            if pending.is_empty() {
+                break;
+            }
+            self.table_multi_get(table, keys, &pending, &mut values)?;
+            pending.retain(|idx| values[*idx].is_none());
+        }
+        // Search on the levels, where the sorted keys are split over the SSTs of each level.
+        for level in snapshot.levels.iter() {
+            let table_of = |idx: &usize| self.find_table_in_level(level, keys[*idx]);
+            for chunk in pending
+                .chunk_by(|a, b| table_of(a).map(|x| x.sst_id()) == table_of(b).map(|x| x.sst_id()))
+            {
+                if let Some(table) = table_of(&chunk[0]) {
+                    self.table_multi_get(table, keys, chunk, &mut values)?;
+                }
+            }
+            pending.retain(|idx| values[*idx].is_none());
+        }
+        Ok(values
+            .into_iter()
+            .map(|value| value.filter(|value| !value.is_empty()))
+            .collect())
+    }
+
+    /// Look up the keys at `indices` in an SST, and fill in the values found.
+    fn table_multi_get(
+        &self,
+        table: &SsTable,
+        keys: &[&[u8]],
+        indices: &[usize],
+        values: &mut [Option<Bytes>],
+    ) -> Result<()> {
+        let candidates: Vec<usize> = indices
+            .iter()
+            .copied()
+            .filter(|idx| self.table_may_contain(table, keys[*idx]))
+            .collect();
+        let table_keys: Vec<&[u8]> = candidates.iter().map(|idx| keys[*idx]).collect();
+        for (idx, value) in candidates.into_iter().zip(table.multi_get(&table_keys)?) {
+            values[idx] = value;
+        }
+        Ok(())
+    }
+
+    /// Find the SST of a level that may contain `key`, i.e., the last one starting at or before it.
+    fn find_table_in_level<'a>(
+        &self,
+        level: &'a [Arc<SsTable>],
+        key: &[u8],
+    ) -> Option<&'a Arc<SsTable>> {
+        let comparator = self.options.comparator.as_ref();
+        let idx = level.partition_point(|table| comparator.compare(table.first_key(), key).is_le());
+        idx.checked_sub(1).map(|idx| &level[idx])
+    }
+
+    /// Check if the filters of an SST may contain `key`.
+    fn table_may_contain(&self, table: &SsTable, key: &[u8]) -> bool {
+        match self.options.prefix_extractor {
+            Some(ref extractor) => extractor
+                .extract(key)
+                .is_none_or(|prefix| table.may_contain_prefix(extractor.as_ref(), prefix)),
+            None => true,
+        }
+    }
+
     /// Put a key-value pair into the storage by writing into the current memtable.
     pub fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
         assert!(!value.is_empty(), "value cannot be empty");
         assert!(!key.is_empty(), "key cannot be empty");
 
-        let guard = self.inner.read();
-        guard.memtable.put(key, value);
+        self.stall_writes()?;
+        let _key_lock = self.lock_key(key);
+        self.write(key, value);
 
         Ok(())
     }
@@ -116,16 +395,115 @@ impl LsmStorage {
     pub fn delete(&self, key: &[u8]) -> Result<()> {
         assert!(!key.is_empty(), "key cannot be empty");
 
-        let guard = self.inner.read();
-        guard.memtable.put(key, b"");
+        self.stall_writes()?;
+        let _key_lock = self.lock_key(key);
+        self.write(key, b"");
 
         Ok(())
     }
 
+    /// Put a key-value pair only if the key does not exist. Returns whether the value is written.
+    pub fn put_if_absent(&self, key: &[u8], value: &[u8]) -> Result<bool> {
+        self.compare_and_swap(key, None, value)
+    }
+
+    /// Put a key-value pair only if the current value of the key is `expected`, where `None`
+    /// means that the key does not exist. Returns whether the value is written.
+    pub fn compare_and_swap(
+        &self,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        new: &[u8],
+    ) -> Result<bool> {
+        assert!(!new.is_empty(), "value cannot be empty");
+        assert!(!key.is_empty(), "key cannot be empty");
+
+        self.stall_writes()?;
+        let _key_lock = self.lock_key(key);
+        if self.get(key)?.as_deref() != expected {
+            return Ok(false);
+        }
+        self.write(key, new);
+
+        Ok(true)
+    }
+
+    /// Remove a key only if its current value is `expected`. Returns whether the key is removed.
+    pub fn delete_if_equals(&self, key: &[u8], expected: &[u8]) -> Result<bool> {
+        assert!(!key.is_empty(), "key cannot be empty");
+
+        self.stall_writes()?;
+        let _key_lock = self.lock_key(key);
+        if self.get(key)?.as_deref() != Some(expected) {
+            return Ok(false);
+        }
+        self.write(key, b"");
+
+        Ok(true)
+    }
+
+    /// Lock the stripe of `key` against other writes.
+    fn lock_key(&self, key: &[u8]) -> MutexGuard<'_, ()> {
+        self.key_locks[(key_hash(key) % KEY_LOCK_STRIPES as u64) as usize].lock()
+    }
+
+    /// Write into the current memtable. The caller must hold the lock of the key.
+    fn write(&self, key: &[u8], value: &[u8]) {
+        let guard = self.inner.read();
+        guard.memtable.put(key, value);
+    }
+
+    /// Create an SST builder configured by the options, for a table written to `level`. The
+    /// builder streams to the file of the next SST ID, which is returned with it.
+    fn new_sst_builder(
+        &self,
+        reason: TableCreationReason,
+        level: usize,
+    ) -> Result<(usize, SsTableBuilder)> {
+        let mut builder = SsTableBuilder::new(self.options.block_size)
+            .with_comparator(self.options.comparator.clone())
+            .with_compression(self.options.compression_of_level(level))
+            .with_creation_reason(reason, level);
+        if let Some(partition_size) = self.options.index_partition_size {
+            builder = builder.with_partitioned_index(partition_size);
+        }
+        if self.options.block_hash_index {
+            builder = builder.with_block_hash_index();
+        }
+        if let Some(ref extractor) = self.options.prefix_extractor {
+            builder = builder
+                .with_prefix_bloom(extractor.clone(), self.options.prefix_bloom_bits_per_key);
+        }
+        if let Some(suffix_bytes) = self.options.range_filter_suffix_bytes {
+            builder = builder.with_range_filter(suffix_bytes);
+        }
+        if let Some(ref rate_limiter) = self.options.rate_limiter {
+            let priority = match reason {
+                TableCreationReason::Flush => IoPriority::High,
+                _ => IoPriority::Low,
+            };
+            builder = builder.with_rate_limiter(rate_limiter.clone(), priority);
+        }
+        let sst_id = self.next_sst_id.fetch_add(1, Ordering::Relaxed);
+        let builder = builder
+            .with_fsync()
+            .with_streaming_output(self.path_of_sst(sst_id))?;
+        Ok((sst_id, builder))
+    }
+
     fn path_of_sst(&self, id: usize) -> PathBuf {
         self.path.join(format!("{:05}.sst", id))
     }
 
+    /// Build an SST created by `new_sst_builder`.
+    fn build_sst(&self, sst_id: usize, builder: SsTableBuilder) -> Result<Arc<SsTable>> {
+        Ok(Arc::new(builder.build(
+            sst_id,
+            Some(self.block_cache.clone()),
+            self.path_of_sst(sst_id),
+        )?))
+    }
+
     /// Persist data to disk.
     ///
     /// In day 3: flush the current memtable to disk as L0 SST.
@@ -134,16 +512,19 @@ impl LsmStorage {
         let _flush_lock = self.flush_lock.lock();
 
         let flush_memtable;
-        let sst_id;
 
         // Move mutable memtable to immutable memtables.
         {
             let mut guard = self.inner.write();
             // Swap the current memtable with a new one.
             let mut snapshot = guard.as_ref().clone();
-            let memtable = std::mem::replace(&mut snapshot.memtable, Arc::new(MemTable::create()));
+            let memtable = std::mem::replace(
+                &mut snapshot.memtable,
+                Arc::new(MemTable::create_with_comparator(
+                    self.options.comparator.clone(),
+                )),
+            );
             flush_memtable = memtable.clone();
-            sst_id = snapshot.next_sst_id;
             // Add the memtable to the immutable memtables.
             snapshot.imm_memtables.push(memtable);
             // Update the snapshot.
@@ -154,13 +535,9 @@ impl LsmStorage {
         // should be operating on the new memtable. We can safely flush the old memtable to
         // disk.
 
-        let mut builder = SsTableBuilder::new(4096);
+        let (sst_id, mut builder) = self.new_sst_builder(TableCreationReason::Flush, 0)?;
         flush_memtable.flush(&mut builder)?;
-        let sst = Arc::new(builder.build(
-            sst_id,
-            Some(self.block_cache.clone()),
-            self.path_of_sst(sst_id),
-        )?);
+        let sst = self.build_sst(sst_id, builder)?;
 
         // Add the flushed L0 table to the list.
         {
@@ -170,11 +547,10 @@ impl LsmStorage {
             snapshot.imm_memtables.pop();
             // Add L0 table
             snapshot.l0_sstables.push(sst);
-            // Update SST ID
-            snapshot.next_sst_id += 1;
             // Update the snapshot.
             *guard = Arc::new(snapshot);
         }
+        self.notify_write_stall();
 
         Ok(())
     }
@@ -185,45 +561,350 @@ impl LsmStorage {
         lower: Bound<&[u8]>,
         upper: Bound<&[u8]>,
     ) -> Result<FusedIterator<LsmIterator>> {
-        let snapshot = {
-            let guard = self.inner.read();
-            Arc::clone(&guard)
-        }; // drop global lock here
+        let (range_lower, range_upper) = (map_bound(lower), map_bound(upper));
+        self.scan_with_table_filter(lower, upper, move |table| {
+            table.may_contain_range(
+                range_lower.as_ref().map(|x| &x[..]),
+                range_upper.as_ref().map(|x| &x[..]),
+            )
+        })
+    }
+
+    /// Scan a page of a range of keys, which is limited by `options`. If there are more keys in the
+    /// range, the page carries a continuation token, which lets the next page resume right after
+    /// it.
+    pub fn scan_with_options(
+        &self,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+        options: &ScanOptions,
+    ) -> Result<ScanPage> {
+        if options.limit == Some(0) {
+            bail!("the limit of a scan page must be at least 1");
+        }
+        let lower = match options.continuation {
+            Some(ref token) => token.bound(),
+            None => lower,
+        };
+        let mut iter = self.scan(lower, upper)?;
+        let mut entries = Vec::new();
+        let mut bytes = 0;
+        while iter.is_valid() {
+            if options.limit.is_some_and(|limit| entries.len() >= limit) {
+                break;
+            }
+            let key = iter.key_bytes();
+            let value = if options.key_only {
+                Bytes::new()
+            } else {
+                iter.value_bytes()
+            };
+            let size = key.len() + value.len();
+            if !entries.is_empty()
+                && options
+                    .max_bytes
+                    .is_some_and(|max_bytes| bytes + size > max_bytes)
+            {
+                break;
+            }
+            bytes += size;
+            entries.push((key, value));
+            iter.next()?;
+        }
+        // A page holds at least one entry, so the next page always starts after this one.
+        let continuation = entries
+            .last()
+            .filter(|_| iter.is_valid())
+            .map(|(key, _)| ContinuationToken::new(Bound::Excluded(key.clone())));
+        Ok(ScanPage {
+            entries,
+            continuation,
+        })
+    }
 
-        let mut memtable_iters = Vec::new();
-        memtable_iters.reserve(snapshot.imm_memtables.len() + 1);
+    /// Collect up to `limit` key-value pairs within `range`.
+    pub fn scan_collect<'a>(
+        &self,
+        range: impl RangeBounds<&'a [u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Bytes, Bytes)>> {
+        let lower = range.start_bound().map(|x| *x);
+        let upper = range.end_bound().map(|x| *x);
+        self.scan(lower, upper)?.into_iter().take(limit).collect()
+    }
+
+    /// Estimate the size of the data within `range` from the indexes of the SSTs, without reading
+    /// any data block. The size of the SSTs is their on-disk size, which may be compressed. If
+    /// `include_memtables` is set, the keys and values within the range in the memtables are added.
+    pub fn approximate_size<'a>(
+        &self,
+        range: impl RangeBounds<&'a [u8]>,
+        include_memtables: bool,
+    ) -> Result<u64> {
+        let lower = range.start_bound().map(|x| *x);
+        let upper = range.end_bound().map(|x| *x);
+        let snapshot = self.inner.read().clone();
+        let mut size = 0;
+        for table in Self::all_sstables(&snapshot) {
+            size += table.approximate_size(lower, upper)?;
+        }
+        if include_memtables {
+            size += Self::memtable_stats(&snapshot, lower, upper).0;
+        }
+        Ok(size)
+    }
+
+    /// Estimate the number of entries within `range` from the indexes and the properties of the
+    /// SSTs, without reading any data block. Overwritten keys and tombstones are counted. If
+    /// `include_memtables` is set, the entries within the range in the memtables are added.
+    pub fn approximate_count<'a>(
+        &self,
+        range: impl RangeBounds<&'a [u8]>,
+        include_memtables: bool,
+    ) -> Result<u64> {
+        let lower = range.start_bound().map(|x| *x);
+        let upper = range.end_bound().map(|x| *x);
+        let snapshot = self.inner.read().clone();
+        let mut count = 0;
+        for table in Self::all_sstables(&snapshot) {
+            count += table.approximate_count(lower, upper)?;
+        }
+        if include_memtables {
+            count += Self::memtable_stats(&snapshot, lower, upper).1;
+        }
+        Ok(count)
+    }
+
+    /// Suggest up to `n - 1` keys which split `range` into `n` parts with roughly the same size of
+    /// data, from the first keys and the sizes of the data blocks in the SSTs. A split key starts a
+    /// part, and is strictly after the start of the range. Fewer keys are returned if the range
+    /// has too few blocks, and memtables are not taken into account.
+    pub fn suggest_split_keys<'a>(
+        &self,
+        range: impl RangeBounds<&'a [u8]>,
+        n: usize,
+    ) -> Result<Vec<Bytes>> {
+        let lower = range.start_bound().map(|x| *x);
+        let upper = range.end_bound().map(|x| *x);
+        let snapshot = self.inner.read().clone();
+        self.split_keys_by_size(Self::all_sstables(&snapshot), lower, upper, n)
+    }
+
+    /// Find up to `n - 1` keys which split the data of `tables` within the range into `n` parts
+    /// of roughly the same size, from the first keys and the sizes of their data blocks.
+    fn split_keys_by_size<'a>(
+        &self,
+        tables: impl Iterator<Item = &'a Arc<SsTable>>,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+        n: usize,
+    ) -> Result<Vec<Bytes>> {
+        let comparator = self.options.comparator.as_ref();
+        let mut blocks = Vec::new();
+        for table in tables {
+            blocks.extend(table.blocks_in_range(lower, upper)?);
+        }
+        blocks.sort_by(|(a, _), (b, _)| comparator.compare(&a.first_key, &b.first_key));
+        let total: u64 = blocks.iter().map(|(_, size)| size).sum();
+
+        let after_lower = |key: &[u8]| match lower {
+            Bound::Included(lower) | Bound::Excluded(lower) => {
+                comparator.compare(key, lower).is_gt()
+            }
+            Bound::Unbounded => true,
+        };
+        let mut split_keys: Vec<Bytes> = Vec::new();
+        let mut before = 0;
+        for (meta, size) in blocks {
+            if split_keys.len() + 1 >= n {
+                break;
+            }
+            // The next split is due once the data before this block reaches its share.
+            let target = total * (split_keys.len() as u64 + 1) / n as u64;
+            if before >= target
+                && before > 0
+                && after_lower(&meta.first_key)
+                && split_keys
+                    .last()
+                    .is_none_or(|last| comparator.compare(last, &meta.first_key).is_lt())
+            {
+                split_keys.push(meta.first_key);
+            }
+            before += size;
+        }
+        Ok(split_keys)
+    }
+
+    /// All SSTs of a snapshot, from L0 to the bottom level.
+    fn all_sstables(snapshot: &LsmStorageInner) -> impl Iterator<Item = &Arc<SsTable>> {
+        snapshot
+            .l0_sstables
+            .iter()
+            .chain(snapshot.levels.iter().flatten())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn sstables_for_test(&self) -> Vec<Arc<SsTable>> {
+        Self::all_sstables(&self.inner.read()).cloned().collect()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn num_cached_blocks_for_test(&self) -> usize {
+        self.block_cache.iter().count()
+    }
+
+    /// The size of the keys and values and the number of entries within the range in the memtables
+    /// of a snapshot.
+    fn memtable_stats(
+        snapshot: &LsmStorageInner,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+    ) -> (u64, u64) {
+        let (mut size, mut count) = (0, 0);
+        for memtable in std::iter::once(&snapshot.memtable).chain(snapshot.imm_memtables.iter()) {
+            let mut iter = memtable.scan(lower, upper);
+            while iter.is_valid() {
+                size += (iter.key().len() + iter.value().len()) as u64;
+                count += 1;
+                // Iterating a memtable never fails.
+                iter.next().unwrap();
+            }
+        }
+        (size, count)
+    }
+
+    /// Create an iterator over all keys starting with `prefix`. If a prefix extractor is set and
+    /// extracts a prefix from `prefix`, SSTs whose prefix bloom filter rules it out are skipped.
+    pub fn prefix_scan(&self, prefix: &[u8]) -> Result<FusedIterator<LsmIterator>> {
+        if !comparator::is_bytewise(self.options.comparator.as_ref()) {
+            bail!("prefix scans need the bytewise comparator");
+        }
+        let upper = prefix_upper_bound(prefix);
+        let upper = match upper {
+            Bound::Excluded(ref key) => Bound::Excluded(&key[..]),
+            _ => Bound::Unbounded,
+        };
+        let filter_prefix = self
+            .options
+            .prefix_extractor
+            .as_ref()
+            .and_then(|extractor| {
+                Some((
+                    extractor.clone(),
+                    Bytes::copy_from_slice(extractor.extract(prefix)?),
+                ))
+            });
+        self.scan_with_table_filter(Bound::Included(prefix), upper, move |table| {
+            filter_prefix.as_ref().is_none_or(|(extractor, prefix)| {
+                table.may_contain_prefix(extractor.as_ref(), prefix)
+            })
+        })
+    }
+
+    /// Create an iterator over a range of keys, only reading the SSTs accepted by `table_filter`.
+    /// The iterator keeps the filter to rebuild itself from newer snapshots.
+    fn scan_with_table_filter(
+        &self,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+        table_filter: impl Fn(&SsTable) -> bool + Send + Sync + 'static,
+    ) -> Result<FusedIterator<LsmIterator>> {
+        let state = self.inner.clone();
+        let comparator = self.options.comparator.clone();
+        let end_bound = map_bound(upper);
+        let builder = {
+            let comparator = comparator.clone();
+            let end_bound = end_bound.clone();
+            move |lower: Bound<&[u8]>| {
+                let snapshot = {
+                    let guard = state.read();
+                    Arc::clone(&guard)
+                }; // drop global lock here
+                Self::create_inner_iter(
+                    &snapshot,
+                    lower,
+                    end_bound.as_ref().map(|x| &x[..]),
+                    &table_filter,
+                    &comparator,
+                )
+            }
+        };
+        Ok(FusedIterator::new(LsmIterator::new(
+            Box::new(builder),
+            lower,
+            end_bound,
+            comparator,
+        )?))
+    }
+
+    /// Create the merged iterator over the memtables and SSTs of a snapshot.
+    fn create_inner_iter(
+        snapshot: &LsmStorageInner,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+        table_filter: &dyn Fn(&SsTable) -> bool,
+        comparator: &Arc<dyn Comparator>,
+    ) -> Result<LsmIteratorInner> {
+        let mut memtable_iters = Vec::with_capacity(snapshot.imm_memtables.len() + 1);
         memtable_iters.push(Box::new(snapshot.memtable.scan(lower, upper)));
         for memtable in snapshot.imm_memtables.iter().rev() {
             memtable_iters.push(Box::new(memtable.scan(lower, upper)));
         }
-        let memtable_iter = MergeIterator::create(memtable_iters);
+        let memtable_iter =
+            MergeIterator::create_with_comparator(memtable_iters, comparator.clone());
 
-        let mut table_iters = Vec::new();
-        table_iters.reserve(snapshot.l0_sstables.len());
+        let mut table_iters = Vec::with_capacity(snapshot.l0_sstables.len());
         for table in snapshot.l0_sstables.iter().rev() {
-            let iter = match lower {
-                Bound::Included(key) => {
+            if !table_filter(table) {
+                continue;
+            }
+            let mut iter = match lower {
+                Bound::Included(key) | Bound::Excluded(key) => {
                     SsTableIterator::create_and_seek_to_key(table.clone(), key)?
                 }
-                Bound::Excluded(key) => {
-                    let mut iter = SsTableIterator::create_and_seek_to_key(table.clone(), key)?;
-                    if iter.is_valid() && iter.key() == key {
-                        iter.next()?;
-                    }
-                    iter
-                }
                 Bound::Unbounded => SsTableIterator::create_and_seek_to_first(table.clone())?,
             };
-
+            Self::skip_excluded(&mut iter, lower)?;
             table_iters.push(Box::new(iter));
         }
-        let table_iter = MergeIterator::create(table_iters);
+        let table_iter = MergeIterator::create_with_comparator(table_iters, comparator.clone());
 
-        let iter = TwoMergeIterator::create(memtable_iter, table_iter)?;
+        let mut level_iters = Vec::with_capacity(snapshot.levels.len());
+        for level in snapshot.levels.iter() {
+            let tables: Vec<Arc<SsTable>> = level
+                .iter()
+                .filter(|table| table_filter(table))
+                .cloned()
+                .collect();
+            let mut iter = match lower {
+                Bound::Included(key) | Bound::Excluded(key) => {
+                    SstConcatIterator::create_and_seek_to_key(tables, key)?
+                }
+                Bound::Unbounded => SstConcatIterator::create_and_seek_to_first(tables)?,
+            };
+            Self::skip_excluded(&mut iter, lower)?;
+            level_iters.push(Box::new(iter));
+        }
+        let level_iter = MergeIterator::create_with_comparator(level_iters, comparator.clone());
 
-        Ok(FusedIterator::new(LsmIterator::new(
-            iter,
-            map_bound(upper),
-        )?))
+        TwoMergeIterator::create_with_comparator(
+            TwoMergeIterator::create_with_comparator(
+                memtable_iter,
+                table_iter,
+                comparator.clone(),
+            )?,
+            level_iter,
+            comparator.clone(),
+        )
+    }
+
+    /// Move an iterator seeked to the key of an excluded lower bound past the key.
+    fn skip_excluded(iter: &mut impl StorageIterator, lower: Bound<&[u8]>) -> Result<()> {
+        if let Bound::Excluded(key) = lower {
+            if iter.is_valid() && iter.key() == key {
+                iter.next()?;
+            }
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,402 @@
+use std::collections::HashSet;
+use std::ops::Bound;
+use std::sync::Arc;
+
+use anyhow::Result;
+use bytes::Bytes;
+
+use super::{LsmStorage, LsmStorageInner, NUM_LEVELS};
+use crate::comparator::Comparator;
+use crate::durable;
+use crate::iterators::concat_iterator::SstConcatIterator;
+use crate::iterators::merge_iterator::MergeIterator;
+use crate::iterators::two_merge_iterator::TwoMergeIterator;
+use crate::iterators::StorageIterator;
+use crate::table::{SsTable, SsTableIterator, TableCreationReason};
+
+/// The SSTs compacted together into the bottom level.
+#[derive(Default)]
+struct CompactionTask {
+    /// L0 SSTs, from earliest to latest.
+    l0_sstables: Vec<Arc<SsTable>>,
+    /// SSTs of L1 - L6, sorted by key range within each level.
+    levels: Vec<Vec<Arc<SsTable>>>,
+    /// SSTs moved to the bottom level as they are, as they overlap no other input and have
+    /// nothing to drop.
+    trivial_moves: Vec<Arc<SsTable>>,
+}
+
+impl CompactionTask {
+    fn is_empty(&self) -> bool {
+        self.l0_sstables.is_empty()
+            && self.levels.iter().all(|level| level.is_empty())
+            && self.trivial_moves.is_empty()
+    }
+
+    /// The IDs of the SSTs merged by the task, which are deleted once it is done.
+    fn merged_sst_ids(&self) -> HashSet<usize> {
+        self.l0_sstables
+            .iter()
+            .chain(self.levels.iter().flatten())
+            .map(|table| table.sst_id())
+            .collect()
+    }
+
+    /// The IDs of all SSTs of the task.
+    fn sst_ids(&self) -> HashSet<usize> {
+        let mut sst_ids = self.merged_sst_ids();
+        sst_ids.extend(self.trivial_moves.iter().map(|table| table.sst_id()));
+        sst_ids
+    }
+}
+
+/// A key range, which grows to cover the SSTs picked for a compaction.
+struct KeySpan<'a> {
+    lower: Bound<Bytes>,
+    upper: Bound<Bytes>,
+    comparator: &'a dyn Comparator,
+}
+
+impl KeySpan<'_> {
+    /// Check if the keys from `first` to `last` overlap the span.
+    fn overlaps(&self, first: &[u8], last: &[u8]) -> bool {
+        let after_upper = match self.upper {
+            Bound::Included(ref key) => self.comparator.compare(first, key).is_gt(),
+            Bound::Excluded(ref key) => self.comparator.compare(first, key).is_ge(),
+            Bound::Unbounded => false,
+        };
+        let before_lower = match self.lower {
+            Bound::Included(ref key) => self.comparator.compare(last, key).is_lt(),
+            Bound::Excluded(ref key) => self.comparator.compare(last, key).is_le(),
+            Bound::Unbounded => false,
+        };
+        !after_upper && !before_lower
+    }
+
+    /// Grow the span to cover the keys from `first` to `last`.
+    fn extend(&mut self, first: &[u8], last: &[u8]) {
+        let extend_lower = match self.lower {
+            Bound::Included(ref key) | Bound::Excluded(ref key) => {
+                self.comparator.compare(first, key).is_le()
+            }
+            Bound::Unbounded => false,
+        };
+        if extend_lower {
+            self.lower = Bound::Included(Bytes::copy_from_slice(first));
+        }
+        let extend_upper = match self.upper {
+            Bound::Included(ref key) | Bound::Excluded(ref key) => {
+                self.comparator.compare(last, key).is_ge()
+            }
+            Bound::Unbounded => false,
+        };
+        if extend_upper {
+            self.upper = Bound::Included(Bytes::copy_from_slice(last));
+        }
+    }
+}
+
+impl LsmStorage {
+    /// Compact all data within the range into the bottom level, dropping tombstones and
+    /// overwritten values, and block until the compaction is done. The memtable is flushed first
+    /// if it has keys within the range.
+    ///
+    /// Along with the SSTs overlapping the range, all SSTs overlapping those are compacted too, so
+    /// that no older version of a key is left above the bottom level.
+    pub fn compact_range(&self, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> Result<()> {
+        let _compaction_lock = self.compaction_lock.lock();
+
+        let memtable = self.inner.read().memtable.clone();
+        if memtable.scan(lower, upper).is_valid() {
+            self.sync()?;
+        }
+
+        let snapshot = {
+            let guard = self.inner.read();
+            Arc::clone(&guard)
+        }; // drop global lock here
+        let task = self.pick_range_compaction(&snapshot, lower, upper)?;
+        if task.is_empty() {
+            return Ok(());
+        }
+        let mut output = self.compact(&task)?;
+        output.extend(task.trivial_moves.iter().cloned());
+
+        // Replace the inputs with the output, keeping the SSTs flushed in the meantime.
+        let sst_ids = task.sst_ids();
+        {
+            let mut guard = self.inner.write();
+            let mut snapshot = guard.as_ref().clone();
+            snapshot
+                .l0_sstables
+                .retain(|table| !sst_ids.contains(&table.sst_id()));
+            for level in snapshot.levels.iter_mut() {
+                level.retain(|table| !sst_ids.contains(&table.sst_id()));
+            }
+            let bottom_level = snapshot.levels.last_mut().unwrap();
+            bottom_level.extend(output);
+            let comparator = self.options.comparator.as_ref();
+            bottom_level.sort_by(|a, b| comparator.compare(a.first_key(), b.first_key()));
+            *guard = Arc::new(snapshot);
+        }
+        self.notify_write_stall();
+
+        // The compaction is applied, so a file that cannot be removed is only left behind, rather
+        // than failing the compaction.
+        for sst_id in task.merged_sst_ids() {
+            let _ = std::fs::remove_file(self.path_of_sst(sst_id));
+        }
+        let _ = durable::sync_dir(&self.path);
+        Ok(())
+    }
+
+    /// Pick the SSTs overlapping the range, and then the ones overlapping those, until the picked
+    /// SSTs cover a key span which no other SST overlaps. The picked SSTs that overlap no other
+    /// picked SST and have no tombstones are moved to the bottom level without being rewritten,
+    /// e.g., the SSTs flushed while keys are written in order.
+    fn pick_range_compaction(
+        &self,
+        snapshot: &LsmStorageInner,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+    ) -> Result<CompactionTask> {
+        let mut span = KeySpan {
+            lower: lower.map(Bytes::copy_from_slice),
+            upper: upper.map(Bytes::copy_from_slice),
+            comparator: self.options.comparator.as_ref(),
+        };
+        // All SSTs with their last keys, which are read once as each takes a block read.
+        let mut candidates = Vec::new();
+        for table in snapshot
+            .l0_sstables
+            .iter()
+            .chain(snapshot.levels.iter().flatten())
+        {
+            candidates.push((table.clone(), table.last_key()?));
+        }
+        let mut is_picked = vec![false; candidates.len()];
+        loop {
+            let mut extended = false;
+            for ((table, last_key), is_picked) in candidates.iter().zip(is_picked.iter_mut()) {
+                if !*is_picked && span.overlaps(table.first_key(), last_key) {
+                    span.extend(table.first_key(), last_key);
+                    *is_picked = true;
+                    extended = true;
+                }
+            }
+            if !extended {
+                break;
+            }
+        }
+        let picked: Vec<(Arc<SsTable>, Bytes)> = candidates
+            .into_iter()
+            .zip(is_picked)
+            .filter_map(|(candidate, is_picked)| is_picked.then_some(candidate))
+            .collect();
+
+        let comparator = self.options.comparator.as_ref();
+        let overlaps = |(a, a_last): &(Arc<SsTable>, Bytes),
+                        (b, b_last): &(Arc<SsTable>, Bytes)| {
+            comparator.compare(a.first_key(), b_last).is_le()
+                && comparator.compare(b.first_key(), a_last).is_le()
+        };
+        let mut trivial_moves = Vec::new();
+        let mut merged = HashSet::new();
+        for table in picked.iter() {
+            // Dropping tombstones needs the table rewritten.
+            let no_tombstones = table
+                .0
+                .properties()
+                .is_some_and(|properties| properties.num_tombstones == 0);
+            let overlaps_others = picked
+                .iter()
+                .any(|other| other.0.sst_id() != table.0.sst_id() && overlaps(table, other));
+            if no_tombstones && !overlaps_others {
+                trivial_moves.push(table.0.clone());
+            } else {
+                merged.insert(table.0.sst_id());
+            }
+        }
+
+        let is_merged = |table: &&Arc<SsTable>| merged.contains(&table.sst_id());
+        Ok(CompactionTask {
+            trivial_moves,
+            l0_sstables: snapshot
+                .l0_sstables
+                .iter()
+                .filter(is_merged)
+                .cloned()
+                .collect(),
+            levels: snapshot
+                .levels
+                .iter()
+                .map(|level| level.iter().filter(is_merged).cloned().collect())
+                .collect(),
+        })
+    }
+
+    /// Merge the SSTs of a task into new SSTs of the bottom level. The task is split by key
+    /// ranges of roughly the same size of data into subcompactions, which run on their own
+    /// threads, with their outputs concatenated in the order of the ranges.
+    fn compact(&self, task: &CompactionTask) -> Result<Vec<Arc<SsTable>>> {
+        let split_keys = if self.options.max_subcompactions > 1 {
+            let tables = task.l0_sstables.iter().chain(task.levels.iter().flatten());
+            self.split_keys_by_size(
+                tables,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                self.options.max_subcompactions,
+            )?
+        } else {
+            Vec::new()
+        };
+        if split_keys.is_empty() {
+            return self.compact_subrange(task, Bound::Unbounded, Bound::Unbounded);
+        }
+
+        let mut ranges = Vec::with_capacity(split_keys.len() + 1);
+        let mut lower = Bound::Unbounded;
+        for key in split_keys.iter() {
+            ranges.push((lower, Bound::Excluded(&key[..])));
+            lower = Bound::Included(&key[..]);
+        }
+        ranges.push((lower, Bound::Unbounded));
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = ranges
+                .into_iter()
+                .map(|(lower, upper)| {
+                    scope.spawn(move || self.compact_subrange(task, lower, upper))
+                })
+                .collect();
+            let mut output = Vec::new();
+            let mut result = Ok(());
+            for handle in handles {
+                match handle.join().expect("subcompaction panicked") {
+                    Ok(tables) => output.extend(tables),
+                    Err(e) => result = Err(e),
+                }
+            }
+            if result.is_err() {
+                // The failed subcompactions removed their own outputs.
+                self.remove_outputs(&output);
+            }
+            result.map(|_| output)
+        })
+    }
+
+    /// Remove the files of the outputs of a failed compaction, which were never installed.
+    fn remove_outputs(&self, output: &[Arc<SsTable>]) {
+        for table in output {
+            // The error of the compaction is returned instead, and a file that could not be
+            // removed is only a leftover.
+            let _ = std::fs::remove_file(self.path_of_sst(table.sst_id()));
+        }
+    }
+
+    /// Merge the keys of a task within the range into new SSTs of the bottom level. If it fails,
+    /// the SSTs already built are removed.
+    fn compact_subrange(
+        &self,
+        task: &CompactionTask,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+    ) -> Result<Vec<Arc<SsTable>>> {
+        let mut output = Vec::new();
+        match self.compact_subrange_into(task, lower, upper, &mut output) {
+            Ok(()) => Ok(output),
+            Err(e) => {
+                self.remove_outputs(&output);
+                Err(e)
+            }
+        }
+    }
+
+    /// Merge the keys of a task within the range into new SSTs, which are pushed to `output` as
+    /// they are built.
+    fn compact_subrange_into(
+        &self,
+        task: &CompactionTask,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+        output: &mut Vec<Arc<SsTable>>,
+    ) -> Result<()> {
+        let mut l0_iters = Vec::with_capacity(task.l0_sstables.len());
+        for table in task.l0_sstables.iter().rev() {
+            let iter = match lower {
+                Bound::Included(key) | Bound::Excluded(key) => {
+                    SsTableIterator::create_and_seek_to_key(table.clone(), key)?
+                }
+                Bound::Unbounded => SsTableIterator::create_and_seek_to_first(table.clone())?,
+            };
+            l0_iters.push(Box::new(iter));
+        }
+        let mut level_iters = Vec::with_capacity(task.levels.len());
+        for level in task.levels.iter() {
+            let iter = match lower {
+                Bound::Included(key) | Bound::Excluded(key) => {
+                    SstConcatIterator::create_and_seek_to_key(level.clone(), key)?
+                }
+                Bound::Unbounded => SstConcatIterator::create_and_seek_to_first(level.clone())?,
+            };
+            level_iters.push(Box::new(iter));
+        }
+        let mut iter = TwoMergeIterator::create_with_comparator(
+            MergeIterator::create_with_comparator(l0_iters, self.options.comparator.clone()),
+            MergeIterator::create_with_comparator(level_iters, self.options.comparator.clone()),
+            self.options.comparator.clone(),
+        )?;
+        Self::skip_excluded(&mut iter, lower)?;
+
+        // The outputs are in the same level as the SSTs moved as they are, so an output must not
+        // span any of them.
+        let comparator = self.options.comparator.as_ref();
+        let mut boundaries: Vec<&[u8]> = task
+            .trivial_moves
+            .iter()
+            .map(|table| table.first_key())
+            .collect();
+        boundaries.sort_by(|a, b| comparator.compare(a, b));
+        let mut next_boundary = 0;
+
+        let mut builder = None;
+        while iter.is_valid() {
+            let beyond_upper = match upper {
+                Bound::Included(key) => comparator.compare(iter.key(), key).is_gt(),
+                Bound::Excluded(key) => comparator.compare(iter.key(), key).is_ge(),
+                Bound::Unbounded => false,
+            };
+            if beyond_upper {
+                break;
+            }
+            while next_boundary < boundaries.len()
+                && comparator
+                    .compare(iter.key(), boundaries[next_boundary])
+                    .is_gt()
+            {
+                if let Some((sst_id, builder)) = builder.take() {
+                    output.push(self.build_sst(sst_id, builder)?);
+                }
+                next_boundary += 1;
+            }
+            // Nothing older than the inputs is left, so tombstones can be dropped.
+            if !iter.value().is_empty() {
+                let (_, current) = match builder {
+                    Some(ref mut builder) => builder,
+                    None => builder
+                        .insert(self.new_sst_builder(TableCreationReason::Compaction, NUM_LEVELS)?),
+                };
+                current.add(iter.key(), iter.value());
+                // Roll over to a new SST once the current one reaches the target size.
+                if current.estimated_size() >= self.options.target_sst_size {
+                    let (sst_id, builder) = builder.take().unwrap();
+                    output.push(self.build_sst(sst_id, builder)?);
+                }
+            }
+            iter.next()?;
+        }
+        if let Some((sst_id, builder)) = builder {
+            output.push(self.build_sst(sst_id, builder)?);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+
+use super::{LsmStorage, LsmStorageInner};
+
+/// When writes are delayed or stopped because flushing and compaction fall behind. A trigger of
+/// `None` never fires, and all triggers are off by default.
+#[derive(Clone, Debug)]
+pub struct WriteStallOptions {
+    /// Delay writes once L0 has this many SSTs.
+    pub l0_slowdown_trigger: Option<usize>,
+    /// Stop writes once L0 has this many SSTs.
+    pub l0_stop_trigger: Option<usize>,
+    /// Delay writes once this many immutable memtables wait to be flushed.
+    pub imm_memtable_slowdown_trigger: Option<usize>,
+    /// Stop writes once this many immutable memtables wait to be flushed.
+    pub imm_memtable_stop_trigger: Option<usize>,
+    /// Delay writes once the SSTs above the bottom level hold this many bytes of data.
+    pub pending_compaction_bytes_slowdown_trigger: Option<u64>,
+    /// Stop writes once the SSTs above the bottom level hold this many bytes of data.
+    pub pending_compaction_bytes_stop_trigger: Option<u64>,
+    /// How long a delayed write sleeps.
+    pub slowdown_delay: Duration,
+    /// How long a stopped write waits for a flush or a compaction to resume writes, before it
+    /// fails.
+    pub stop_timeout: Duration,
+}
+
+impl Default for WriteStallOptions {
+    fn default() -> Self {
+        Self {
+            l0_slowdown_trigger: None,
+            l0_stop_trigger: None,
+            imm_memtable_slowdown_trigger: None,
+            imm_memtable_stop_trigger: None,
+            pending_compaction_bytes_slowdown_trigger: None,
+            pending_compaction_bytes_stop_trigger: None,
+            slowdown_delay: Duration::from_millis(1),
+            stop_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WriteStallState {
+    /// Writes go through.
+    #[default]
+    Normal,
+    /// Writes are delayed.
+    Delayed,
+    /// Writes wait until a flush or a compaction resumes them.
+    Stopped,
+}
+
+/// Statistics of write stalls.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WriteStallStats {
+    /// The state writes are in now.
+    pub state: WriteStallState,
+    /// Number of delayed writes.
+    pub num_delays: u64,
+    /// Total time writes were delayed.
+    pub delay_duration: Duration,
+    /// Number of stopped writes, including the ones that timed out.
+    pub num_stops: u64,
+    /// Total time writes were stopped.
+    pub stop_duration: Duration,
+    /// Number of stopped writes that timed out.
+    pub num_stop_timeouts: u64,
+}
+
+impl WriteStallOptions {
+    /// The state writes should be in with the SSTs and memtables of a snapshot.
+    fn state_of(&self, snapshot: &LsmStorageInner) -> WriteStallState {
+        let num_l0 = snapshot.l0_sstables.len();
+        let num_imm = snapshot.imm_memtables.len();
+        let pending_bytes: u64 = snapshot
+            .l0_sstables
+            .iter()
+            .chain(
+                snapshot.levels[..snapshot.levels.len() - 1]
+                    .iter()
+                    .flatten(),
+            )
+            .map(|table| table.data_size())
+            .sum();
+        let reached = |trigger: Option<usize>, x: usize| trigger.is_some_and(|t| x >= t);
+        let reached_bytes = |trigger: Option<u64>| trigger.is_some_and(|t| pending_bytes >= t);
+        if reached(self.l0_stop_trigger, num_l0)
+            || reached(self.imm_memtable_stop_trigger, num_imm)
+            || reached_bytes(self.pending_compaction_bytes_stop_trigger)
+        {
+            WriteStallState::Stopped
+        } else if reached(self.l0_slowdown_trigger, num_l0)
+            || reached(self.imm_memtable_slowdown_trigger, num_imm)
+            || reached_bytes(self.pending_compaction_bytes_slowdown_trigger)
+        {
+            WriteStallState::Delayed
+        } else {
+            WriteStallState::Normal
+        }
+    }
+}
+
+impl LsmStorage {
+    /// Delay or stop a write if flushing and compaction fall behind.
+    pub(super) fn stall_writes(&self) -> Result<()> {
+        let options = &self.options.write_stall;
+        let state = options.state_of(&self.inner.read());
+        match state {
+            WriteStallState::Normal => {}
+            WriteStallState::Delayed => {
+                let start = Instant::now();
+                std::thread::sleep(options.slowdown_delay);
+                let mut stats = self.write_stall_stats.lock();
+                stats.num_delays += 1;
+                stats.delay_duration += start.elapsed();
+            }
+            WriteStallState::Stopped => {
+                // The stop is counted as it begins, and its duration once it ends.
+                self.write_stall_stats.lock().num_stops += 1;
+                let start = Instant::now();
+                let deadline = start + options.stop_timeout;
+                let mut guard = self.write_stall_lock.lock();
+                let mut timed_out = false;
+                while options.state_of(&self.inner.read()) == WriteStallState::Stopped {
+                    if self
+                        .write_stall_cond
+                        .wait_until(&mut guard, deadline)
+                        .timed_out()
+                    {
+                        timed_out =
+                            options.state_of(&self.inner.read()) == WriteStallState::Stopped;
+                        break;
+                    }
+                }
+                drop(guard);
+                let mut stats = self.write_stall_stats.lock();
+                stats.stop_duration += start.elapsed();
+                if timed_out {
+                    stats.num_stop_timeouts += 1;
+                    bail!(
+                        "writes stopped for {:?} as flushing and compaction fall behind",
+                        options.stop_timeout
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Wake up the stopped writes after the SSTs or memtables changed.
+    pub(super) fn notify_write_stall(&self) {
+        let _guard = self.write_stall_lock.lock();
+        self.write_stall_cond.notify_all();
+    }
+
+    /// Statistics of write stalls since the storage was opened.
+    pub fn write_stall_stats(&self) -> WriteStallStats {
+        let state = self.options.write_stall.state_of(&self.inner.read());
+        WriteStallStats {
+            state,
+            ..self.write_stall_stats.lock().clone()
+        }
+    }
+}
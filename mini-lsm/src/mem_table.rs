@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use std::ops::Bound;
 use std::sync::Arc;
 
@@ -7,12 +8,79 @@ use crossbeam_skiplist::map::Entry;
 use crossbeam_skiplist::SkipMap;
 use ouroboros::self_referencing;
 
-use crate::iterators::StorageIterator;
+use crate::comparator::{self, Comparator};
+use crate::iterators::{SeekableIterator, StorageIterator};
 use crate::table::SsTableBuilder;
 
+/// A key in a skip list of a mem-table, which is ordered by the comparator of the mem-table.
+trait SkipMapKey: Ord + Send + Sync + 'static {
+    fn new(key: Bytes, comparator: &Arc<dyn Comparator>) -> Self;
+
+    fn key(&self) -> &Bytes;
+}
+
+/// With the bytewise comparator, the keys are the bytes themselves.
+impl SkipMapKey for Bytes {
+    fn new(key: Bytes, _comparator: &Arc<dyn Comparator>) -> Self {
+        key
+    }
+
+    fn key(&self) -> &Bytes {
+        self
+    }
+}
+
+/// With any other comparator, each key holds the comparator that orders it.
+#[derive(Clone, Debug)]
+struct ComparatorKey {
+    key: Bytes,
+    comparator: Arc<dyn Comparator>,
+}
+
+impl PartialEq for ComparatorKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ComparatorKey {}
+
+impl PartialOrd for ComparatorKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ComparatorKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.comparator.compare(&self.key, &other.key)
+    }
+}
+
+impl SkipMapKey for ComparatorKey {
+    fn new(key: Bytes, comparator: &Arc<dyn Comparator>) -> Self {
+        Self {
+            key,
+            comparator: comparator.clone(),
+        }
+    }
+
+    fn key(&self) -> &Bytes {
+        &self.key
+    }
+}
+
+/// The skip list of a mem-table, keyed by the bytes of the keys for the bytewise comparator, so
+/// that only other comparators pay for a comparator in every key.
+enum SkipMaps {
+    Bytewise(Arc<SkipMap<Bytes, Bytes>>),
+    Comparator(Arc<SkipMap<ComparatorKey, Bytes>>),
+}
+
 /// A basic mem-table based on crossbeam-skiplist
 pub struct MemTable {
-    map: Arc<SkipMap<Bytes, Bytes>>,
+    map: SkipMaps,
+    comparator: Arc<dyn Comparator>,
 }
 
 pub(crate) fn map_bound(bound: Bound<&[u8]>) -> Bound<Bytes> {
@@ -26,82 +94,196 @@ pub(crate) fn map_bound(bound: Bound<&[u8]>) -> Bound<Bytes> {
 impl MemTable {
     /// Create a new mem-table.
     pub fn create() -> Self {
-        Self {
-            map: Arc::new(SkipMap::new()),
-        }
+        Self::create_with_comparator(comparator::bytewise())
+    }
+
+    /// Create a new mem-table whose keys are sorted by `comparator`.
+    pub fn create_with_comparator(comparator: Arc<dyn Comparator>) -> Self {
+        let map = if comparator::is_bytewise(comparator.as_ref()) {
+            SkipMaps::Bytewise(Arc::new(SkipMap::new()))
+        } else {
+            SkipMaps::Comparator(Arc::new(SkipMap::new()))
+        };
+        Self { map, comparator }
     }
 
     /// Get a value by key.
     pub fn get(&self, key: &[u8]) -> Option<Bytes> {
-        self.map.get(key).map(|e| e.value().clone())
+        let key = Bytes::copy_from_slice(key);
+        match self.map {
+            SkipMaps::Bytewise(ref map) => map.get(&key).map(|e| e.value().clone()),
+            SkipMaps::Comparator(ref map) => map
+                .get(&ComparatorKey::new(key, &self.comparator))
+                .map(|e| e.value().clone()),
+        }
     }
 
     /// Put a key-value pair into the mem-table.
     pub fn put(&self, key: &[u8], value: &[u8]) {
-        self.map
-            .insert(Bytes::copy_from_slice(key), Bytes::copy_from_slice(value));
+        let key = Bytes::copy_from_slice(key);
+        let value = Bytes::copy_from_slice(value);
+        match self.map {
+            SkipMaps::Bytewise(ref map) => {
+                map.insert(key, value);
+            }
+            SkipMaps::Comparator(ref map) => {
+                map.insert(ComparatorKey::new(key, &self.comparator), value);
+            }
+        }
     }
 
     /// Get an iterator over a range of keys.
     pub fn scan(&self, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> MemTableIterator {
         let (lower, upper) = (map_bound(lower), map_bound(upper));
-        let mut iter = MemTableIteratorBuilder {
-            map: self.map.clone(),
-            iter_builder: |map| map.range((lower, upper)),
-            item: (Bytes::from_static(&[]), Bytes::from_static(&[])),
-        }
-        .build();
-        let entry = iter.with_iter_mut(|iter| MemTableIterator::entry_to_item(iter.next()));
-        iter.with_mut(|x| *x.item = entry);
-        iter
+        let comparator = self.comparator.clone();
+        MemTableIterator(match self.map {
+            SkipMaps::Bytewise(ref map) => SkipMapIterators::Bytewise(SkipMapIterator::create(
+                map.clone(),
+                comparator,
+                lower,
+                upper,
+            )),
+            SkipMaps::Comparator(ref map) => SkipMapIterators::Comparator(SkipMapIterator::create(
+                map.clone(),
+                comparator,
+                lower,
+                upper,
+            )),
+        })
     }
 
     /// Flush the mem-table to SSTable.
     pub fn flush(&self, builder: &mut SsTableBuilder) -> Result<()> {
-        for entry in self.map.iter() {
-            builder.add(&entry.key()[..], &entry.value()[..]);
+        match self.map {
+            SkipMaps::Bytewise(ref map) => {
+                for entry in map.iter() {
+                    builder.add(&entry.key()[..], &entry.value()[..]);
+                }
+            }
+            SkipMaps::Comparator(ref map) => {
+                for entry in map.iter() {
+                    builder.add(&entry.key().key[..], &entry.value()[..]);
+                }
+            }
         }
         Ok(())
     }
 }
 
-type SkipMapRangeIter<'a> =
-    crossbeam_skiplist::map::Range<'a, Bytes, (Bound<Bytes>, Bound<Bytes>), Bytes, Bytes>;
+type SkipMapRangeIter<'a, K> =
+    crossbeam_skiplist::map::Range<'a, K, (Bound<K>, Bound<K>), K, Bytes>;
 
-/// An iterator over a range of `SkipMap`.
+/// An iterator over a range of a `SkipMap`.
 #[self_referencing]
-pub struct MemTableIterator {
-    map: Arc<SkipMap<Bytes, Bytes>>,
+struct SkipMapIterator<K: SkipMapKey> {
+    map: Arc<SkipMap<K, Bytes>>,
     #[borrows(map)]
     #[not_covariant]
-    iter: SkipMapRangeIter<'this>,
+    iter: SkipMapRangeIter<'this, K>,
     item: (Bytes, Bytes),
+    comparator: Arc<dyn Comparator>,
+    /// The upper bound of the range, which is kept when seeking.
+    upper: Bound<Bytes>,
 }
 
-impl MemTableIterator {
-    fn entry_to_item(entry: Option<Entry<'_, Bytes, Bytes>>) -> (Bytes, Bytes) {
+impl<K: SkipMapKey> SkipMapIterator<K> {
+    fn create(
+        map: Arc<SkipMap<K, Bytes>>,
+        comparator: Arc<dyn Comparator>,
+        lower: Bound<Bytes>,
+        upper: Bound<Bytes>,
+    ) -> Self {
+        let to_key = |key| K::new(key, &comparator);
+        let range = (lower.map(to_key), upper.clone().map(to_key));
+        let mut iter = SkipMapIteratorBuilder {
+            map,
+            iter_builder: |map| map.range(range),
+            item: (Bytes::from_static(&[]), Bytes::from_static(&[])),
+            comparator,
+            upper,
+        }
+        .build();
+        iter.advance();
+        iter
+    }
+
+    /// Move to the next entry of the range.
+    fn advance(&mut self) {
+        let entry = self.with_iter_mut(|iter| Self::entry_to_item(iter.next()));
+        self.with_mut(|x| *x.item = entry);
+    }
+
+    fn entry_to_item(entry: Option<Entry<'_, K, Bytes>>) -> (Bytes, Bytes) {
         entry
-            .map(|x| (x.key().clone(), x.value().clone()))
+            .map(|x| (x.key().key().clone(), x.value().clone()))
             .unwrap_or_else(|| (Bytes::from_static(&[]), Bytes::from_static(&[])))
     }
+
+    /// Seek to the first key that is >= `key`, within the upper bound of the range.
+    fn seek_to_key(&mut self, key: &[u8]) {
+        *self = Self::create(
+            self.borrow_map().clone(),
+            self.borrow_comparator().clone(),
+            Bound::Included(Bytes::copy_from_slice(key)),
+            self.borrow_upper().clone(),
+        );
+    }
+}
+
+enum SkipMapIterators {
+    Bytewise(SkipMapIterator<Bytes>),
+    Comparator(SkipMapIterator<ComparatorKey>),
+}
+
+/// An iterator over a range of a mem-table.
+pub struct MemTableIterator(SkipMapIterators);
+
+impl MemTableIterator {
+    fn item(&self) -> &(Bytes, Bytes) {
+        match self.0 {
+            SkipMapIterators::Bytewise(ref iter) => iter.borrow_item(),
+            SkipMapIterators::Comparator(ref iter) => iter.borrow_item(),
+        }
+    }
 }
 
 impl StorageIterator for MemTableIterator {
     fn value(&self) -> &[u8] {
-        &self.borrow_item().1[..]
+        &self.item().1[..]
     }
 
     fn key(&self) -> &[u8] {
-        &self.borrow_item().0[..]
+        &self.item().0[..]
+    }
+
+    fn value_bytes(&self) -> Bytes {
+        self.item().1.clone()
+    }
+
+    fn key_bytes(&self) -> Bytes {
+        self.item().0.clone()
     }
 
     fn is_valid(&self) -> bool {
-        !self.borrow_item().0.is_empty()
+        !self.item().0.is_empty()
     }
 
     fn next(&mut self) -> Result<()> {
-        let entry = self.with_iter_mut(|iter| MemTableIterator::entry_to_item(iter.next()));
-        self.with_mut(|x| *x.item = entry);
+        match self.0 {
+            SkipMapIterators::Bytewise(ref mut iter) => iter.advance(),
+            SkipMapIterators::Comparator(ref mut iter) => iter.advance(),
+        }
+        Ok(())
+    }
+}
+
+impl SeekableIterator for MemTableIterator {
+    /// Seek to the first key that is >= `key`, within the upper bound of the range.
+    fn seek_to_key(&mut self, key: &[u8]) -> Result<()> {
+        match self.0 {
+            SkipMapIterators::Bytewise(ref mut iter) => iter.seek_to_key(key),
+            SkipMapIterators::Comparator(ref mut iter) => iter.seek_to_key(key),
+        }
         Ok(())
     }
 }
@@ -93,3 +93,30 @@ fn test_memtable_iter() {
         assert!(!iter.is_valid());
     }
 }
+
+#[test]
+fn test_memtable_with_comparator() {
+    use std::ops::Bound;
+    use std::sync::Arc;
+
+    use crate::comparator::ReverseBytewiseComparator;
+    use crate::iterators::SeekableIterator;
+
+    let memtable = MemTable::create_with_comparator(Arc::new(ReverseBytewiseComparator));
+    memtable.put(b"key1", b"value1");
+    memtable.put(b"key3", b"value3");
+    memtable.put(b"key2", b"value2");
+    memtable.put(b"key2", b"value22");
+    assert_eq!(&memtable.get(b"key2").unwrap()[..], b"value22");
+    assert!(memtable.get(b"key4").is_none());
+
+    let mut iter = memtable.scan(Bound::Included(b"key3"), Bound::Excluded(b"key1"));
+    assert_eq!(iter.key(), b"key3");
+    iter.next().unwrap();
+    assert_eq!(iter.key(), b"key2");
+    assert_eq!(iter.value(), b"value22");
+    iter.next().unwrap();
+    assert!(!iter.is_valid());
+    iter.seek_to_key(b"key2").unwrap();
+    assert_eq!(iter.key(), b"key2");
+}
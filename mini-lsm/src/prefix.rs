@@ -0,0 +1,80 @@
+use std::fmt::Debug;
+use std::ops::Bound;
+
+/// Extracts the prefix of a key, which is used by prefix bloom filters and prefix scans.
+///
+/// The extractor must be consistent: if `extract(x)` returns `Some(p)`, then `extract(y)` must
+/// return `Some(p)` for every `y` that starts with `x`. Filters record the name of the extractor,
+/// so it must change whenever the extraction changes.
+pub trait PrefixExtractor: Debug + Send + Sync {
+    /// The name of the extractor, persisted with the filters built by it.
+    fn name(&self) -> String;
+
+    /// Extract the prefix of a key, or `None` if the key has no prefix.
+    fn extract<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]>;
+}
+
+/// Uses the first `len` bytes as the prefix. Keys shorter than that have no prefix.
+#[derive(Clone, Debug)]
+pub struct FixedPrefixExtractor {
+    len: usize,
+}
+
+impl FixedPrefixExtractor {
+    pub fn new(len: usize) -> Self {
+        Self { len }
+    }
+}
+
+impl PrefixExtractor for FixedPrefixExtractor {
+    fn name(&self) -> String {
+        format!("fixed:{}", self.len)
+    }
+
+    fn extract<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]> {
+        key.get(..self.len)
+    }
+}
+
+/// Uses everything up to and including the `count`-th delimiter as the prefix, e.g., `tenant/` or
+/// `tenant/entity/` for keys like `tenant/entity/id` and a delimiter of `/`. Keys with fewer
+/// delimiters have no prefix.
+#[derive(Clone, Debug)]
+pub struct DelimitedPrefixExtractor {
+    delimiter: u8,
+    count: usize,
+}
+
+impl DelimitedPrefixExtractor {
+    pub fn new(delimiter: u8, count: usize) -> Self {
+        assert!(count > 0, "count must be positive");
+        Self { delimiter, count }
+    }
+}
+
+impl PrefixExtractor for DelimitedPrefixExtractor {
+    fn name(&self) -> String {
+        format!("delimited:{}:{}", self.delimiter, self.count)
+    }
+
+    fn extract<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]> {
+        let (end, _) = key
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| **x == self.delimiter)
+            .nth(self.count - 1)?;
+        Some(&key[..=end])
+    }
+}
+
+/// The smallest key that is greater than every key starting with `prefix`, as an upper bound.
+pub fn prefix_upper_bound(prefix: &[u8]) -> Bound<Vec<u8>> {
+    let mut upper = prefix.to_vec();
+    while let Some(last) = upper.pop() {
+        if last != u8::MAX {
+            upper.push(last + 1);
+            return Bound::Excluded(upper);
+        }
+    }
+    Bound::Unbounded
+}
@@ -0,0 +1,109 @@
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+
+/// The priority of writes going through a rate limiter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoPriority {
+    /// Writes that foreground writes wait for, e.g., flushes and WAL writes.
+    High,
+    /// Background writes, e.g., compactions, which wait while any high-priority write waits.
+    Low,
+}
+
+/// A token bucket which limits the rate of writes in bytes per second. It can be shared by many
+/// writers and storages, and the rate can be changed at any time.
+///
+/// The bucket holds up to one second worth of bytes. A write is let through as long as the
+/// bucket is not in debt, and takes all the bytes it needs, so that a large write only delays
+/// the ones after it.
+#[derive(Debug)]
+pub struct RateLimiter {
+    state: Mutex<RateLimiterState>,
+    cond: Condvar,
+}
+
+#[derive(Debug)]
+struct RateLimiterState {
+    /// The rate, where 0 means unlimited.
+    bytes_per_sec: u64,
+    /// The bytes in the bucket, which is negative when in debt.
+    available: f64,
+    last_refill: Instant,
+    /// Number of high-priority writes waiting.
+    high_priority_waiters: usize,
+}
+
+impl RateLimiterState {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.available =
+            (self.available + elapsed * self.bytes_per_sec as f64).min(self.bytes_per_sec as f64);
+        self.last_refill = now;
+    }
+}
+
+/// The longest a waiting write sleeps before checking the bucket again.
+const MAX_WAIT: Duration = Duration::from_millis(100);
+
+impl RateLimiter {
+    /// Create a rate limiter, where a rate of 0 means unlimited.
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            state: Mutex::new(RateLimiterState {
+                bytes_per_sec,
+                available: 0.0,
+                last_refill: Instant::now(),
+                high_priority_waiters: 0,
+            }),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// The current rate in bytes per second, 0 if unlimited.
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.state.lock().bytes_per_sec
+    }
+
+    /// Change the rate, which applies to the writes waiting too.
+    pub fn set_bytes_per_sec(&self, bytes_per_sec: u64) {
+        let mut state = self.state.lock();
+        state.refill();
+        state.bytes_per_sec = bytes_per_sec;
+        state.available = state.available.min(bytes_per_sec as f64);
+        self.cond.notify_all();
+    }
+
+    /// Block until `bytes` may be written.
+    pub fn request(&self, bytes: usize, priority: IoPriority) {
+        let mut state = self.state.lock();
+        if priority == IoPriority::High {
+            state.high_priority_waiters += 1;
+        }
+        loop {
+            if state.bytes_per_sec == 0 {
+                break;
+            }
+            state.refill();
+            let yields = priority == IoPriority::Low && state.high_priority_waiters > 0;
+            if state.available >= 0.0 && !yields {
+                state.available -= bytes as f64;
+                break;
+            }
+            let wait = if state.available < 0.0 {
+                Duration::from_secs_f64(-state.available / state.bytes_per_sec as f64)
+            } else {
+                MAX_WAIT
+            };
+            self.cond.wait_for(&mut state, wait.min(MAX_WAIT));
+        }
+        if priority == IoPriority::High {
+            state.high_priority_waiters -= 1;
+            self.cond.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,61 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use super::*;
+
+#[test]
+fn test_rate_limiter_rate() {
+    let limiter = RateLimiter::new(1_000_000);
+    let start = Instant::now();
+    for _ in 0..5 {
+        limiter.request(50_000, IoPriority::Low);
+    }
+    // The first write goes through at once, and each of the others waits for the one before.
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(190), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+
+    let limiter = RateLimiter::new(0);
+    let start = Instant::now();
+    for _ in 0..100 {
+        limiter.request(1 << 20, IoPriority::Low);
+    }
+    assert!(start.elapsed() < Duration::from_millis(100));
+}
+
+#[test]
+fn test_rate_limiter_priority() {
+    let limiter = Arc::new(RateLimiter::new(1_000_000));
+    // Put the bucket in debt for 200ms.
+    limiter.request(200_000, IoPriority::Low);
+    let order = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            limiter.request(100_000, IoPriority::Low);
+            order.lock().push(IoPriority::Low);
+        });
+        std::thread::sleep(Duration::from_millis(20));
+        scope.spawn(|| {
+            limiter.request(100_000, IoPriority::High);
+            order.lock().push(IoPriority::High);
+        });
+    });
+    assert_eq!(*order.lock(), vec![IoPriority::High, IoPriority::Low]);
+}
+
+#[test]
+fn test_rate_limiter_set_rate() {
+    let limiter = RateLimiter::new(1_000);
+    // Put the bucket in debt for 100s.
+    limiter.request(100_000, IoPriority::High);
+    let start = Instant::now();
+    std::thread::scope(|scope| {
+        scope.spawn(|| limiter.request(1, IoPriority::High));
+        std::thread::sleep(Duration::from_millis(20));
+        limiter.set_bytes_per_sec(0);
+    });
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(limiter.bytes_per_sec(), 0);
+}
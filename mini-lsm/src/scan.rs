@@ -0,0 +1,76 @@
+use std::ops::Bound;
+
+use anyhow::{bail, Result};
+use bytes::{BufMut, Bytes};
+
+const BOUND_KIND_INCLUDED: u8 = 0;
+const BOUND_KIND_EXCLUDED: u8 = 1;
+const BOUND_KIND_UNBOUNDED: u8 = 2;
+
+/// Options of `LsmStorage::scan_with_options`, which returns the scan page by page.
+#[derive(Clone, Debug, Default)]
+pub struct ScanOptions {
+    /// The maximum number of entries in a page, which must be at least 1.
+    pub limit: Option<usize>,
+    /// The maximum total size of the keys and values in a page. A page holds at least one entry,
+    /// even if it is larger than this.
+    pub max_bytes: Option<usize>,
+    /// Only return the keys, with empty values.
+    pub key_only: bool,
+    /// Resume from where the previous page stopped, instead of the lower bound of the scan.
+    pub continuation: Option<ContinuationToken>,
+}
+
+/// A page of a scan.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScanPage {
+    pub entries: Vec<(Bytes, Bytes)>,
+    /// Set if the scan has more entries, to be passed in the options of the next page.
+    pub continuation: Option<ContinuationToken>,
+}
+
+/// Where the next page of a scan starts, which is the lower bound of the rest of the scan. It is
+/// opaque to clients, who pass it around as bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContinuationToken {
+    bound: Bound<Bytes>,
+}
+
+impl ContinuationToken {
+    pub(crate) fn new(bound: Bound<Bytes>) -> Self {
+        Self { bound }
+    }
+
+    /// The lower bound of the rest of the scan.
+    pub(crate) fn bound(&self) -> Bound<&[u8]> {
+        self.bound.as_ref().map(|x| &x[..])
+    }
+
+    /// Encode the token as the bound kind followed by the key.
+    pub fn to_bytes(&self) -> Bytes {
+        let (kind, key) = match self.bound {
+            Bound::Included(ref key) => (BOUND_KIND_INCLUDED, &key[..]),
+            Bound::Excluded(ref key) => (BOUND_KIND_EXCLUDED, &key[..]),
+            Bound::Unbounded => (BOUND_KIND_UNBOUNDED, &[][..]),
+        };
+        let mut buf = Vec::with_capacity(key.len() + 1);
+        buf.put_u8(kind);
+        buf.put_slice(key);
+        buf.into()
+    }
+
+    /// Decode a token encoded by `to_bytes`.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let Some((kind, key)) = data.split_first() else {
+            bail!("empty continuation token");
+        };
+        let key = Bytes::copy_from_slice(key);
+        let bound = match *kind {
+            BOUND_KIND_INCLUDED if !key.is_empty() => Bound::Included(key),
+            BOUND_KIND_EXCLUDED if !key.is_empty() => Bound::Excluded(key),
+            BOUND_KIND_UNBOUNDED if key.is_empty() => Bound::Unbounded,
+            _ => bail!("invalid continuation token"),
+        };
+        Ok(Self { bound })
+    }
+}
@@ -1,17 +1,40 @@
+mod bloom;
 mod builder;
+mod compression;
+mod filter;
+mod footer;
+mod index;
 mod iterator;
+mod properties;
+mod range_filter;
+mod writer;
 
 use std::fs::File;
+use std::ops::Bound;
 use std::path::Path;
 use std::sync::Arc;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
+pub use bloom::Bloom;
 pub use builder::SsTableBuilder;
 use bytes::{Buf, BufMut, Bytes};
+pub use compression::CompressionType;
+pub use filter::{PrefixBloom, TableFilters};
+pub use footer::{
+    Footer, CURRENT_FORMAT_VERSION, FORMAT_VERSION_LEGACY, FORMAT_VERSION_V1, FORMAT_VERSION_V2,
+    FORMAT_VERSION_V3, SST_MAGIC,
+};
+use index::PARTITIONED_INDEX_TRAILER_SIZE;
+pub use index::{IndexPartitionMeta, PartitionedIndex, INDEX_TYPE_FLAT, INDEX_TYPE_PARTITIONED};
 pub use iterator::SsTableIterator;
+pub use properties::{TableCreationReason, TableProperties};
+pub use range_filter::{RangeFilter, RangeFilterBuilder};
+use writer::TableWriter;
 
 use crate::block::Block;
-use crate::lsm_storage::BlockCache;
+use crate::comparator::{self, Comparator};
+use crate::lsm_storage::{BlockCache, BlockCacheKey};
+use crate::prefix::PrefixExtractor;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BlockMeta {
@@ -95,13 +118,12 @@ impl FileObject {
         self.1
     }
 
-    /// Create a new file object (day 2) and write the file to the disk (day 4).
+    /// Create a new file object (day 2) and write the file to the disk durably (day 4). The file
+    /// is written by `TableWriter`, the same as the SSTs streamed out by `SsTableBuilder`.
     pub fn create(path: &Path, data: Vec<u8>) -> Result<Self> {
-        std::fs::write(path, &data)?;
-        Ok(FileObject(
-            File::options().read(true).write(false).open(path)?,
-            data.len() as u64,
-        ))
+        let mut writer = TableWriter::create(path)?;
+        writer.write(&data, None)?;
+        writer.finish(true)
     }
 
     pub fn open(_path: &Path) -> Result<Self> {
@@ -111,8 +133,16 @@ impl FileObject {
 
 pub struct SsTable {
     file: FileObject,
+    /// The block metas of a flat index, empty if the index is partitioned.
     block_metas: Vec<BlockMeta>,
+    /// The top-level index if the index is partitioned.
+    partitioned_index: Option<PartitionedIndex>,
     block_meta_offset: usize,
+    footer: Footer,
+    filters: TableFilters,
+    properties: Option<TableProperties>,
+    /// The order of the keys in the table.
+    comparator: Arc<dyn Comparator>,
     id: usize,
     block_cache: Option<Arc<BlockCache>>,
 }
@@ -125,37 +155,159 @@ impl SsTable {
 
     /// Open SSTable from a file.
     pub fn open(id: usize, block_cache: Option<Arc<BlockCache>>, file: FileObject) -> Result<Self> {
-        let len = file.size();
-        let raw_meta_offset = file.read(len - 4, 4)?;
-        let block_meta_offset = (&raw_meta_offset[..]).get_u32() as u64;
-        let raw_meta = file.read(block_meta_offset, len - 4 - block_meta_offset)?;
+        Self::open_with_comparator(id, block_cache, file, comparator::bytewise())
+    }
+
+    /// Open SSTable from a file, whose keys are sorted by `comparator`.
+    pub fn open_with_comparator(
+        id: usize,
+        block_cache: Option<Arc<BlockCache>>,
+        file: FileObject,
+        comparator: Arc<dyn Comparator>,
+    ) -> Result<Self> {
+        let footer = Footer::read(&file)?;
+        let (block_metas, partitioned_index) = match footer.version {
+            FORMAT_VERSION_LEGACY | FORMAT_VERSION_V1 | FORMAT_VERSION_V2 => {
+                let raw_meta = file.read(
+                    footer.meta_offset,
+                    footer.filter_offset - footer.meta_offset,
+                )?;
+                (BlockMeta::decode_block_meta(&raw_meta[..]), None)
+            }
+            FORMAT_VERSION_V3 => Self::read_index(&file, &footer)?,
+            version => bail!("unsupported SST format version {}", version),
+        };
+        let filters = if footer.filter_offset < footer.properties_offset {
+            let raw_filters = file.read(
+                footer.filter_offset,
+                footer.properties_offset - footer.filter_offset,
+            )?;
+            TableFilters::decode(&raw_filters[..])?
+        } else {
+            TableFilters::default()
+        };
+        let properties_end = file.size() - footer.encoded_len();
+        let properties = if footer.properties_offset < properties_end {
+            let raw_properties = file.read(
+                footer.properties_offset,
+                properties_end - footer.properties_offset,
+            )?;
+            Some(TableProperties::decode(&raw_properties[..])?)
+        } else {
+            None
+        };
         Ok(Self {
             file,
-            block_metas: BlockMeta::decode_block_meta(&raw_meta[..]),
-            block_meta_offset: block_meta_offset as usize,
+            block_metas,
+            partitioned_index,
+            block_meta_offset: footer.meta_offset as usize,
+            footer,
+            filters,
+            properties,
+            comparator,
             id,
             block_cache,
         })
     }
 
-    /// Read a block from the disk.
-    pub fn read_block(&self, block_idx: usize) -> Result<Arc<Block>> {
+    /// Read the meta section of a table whose meta section ends with an index type byte.
+    fn read_index(
+        file: &FileObject,
+        footer: &Footer,
+    ) -> Result<(Vec<BlockMeta>, Option<PartitionedIndex>)> {
+        let meta_end = footer.filter_offset;
+        if meta_end == footer.meta_offset {
+            bail!("corrupted SST: empty meta section");
+        }
+        let index_type = file.read(meta_end - 1, 1)?[0];
+        match index_type {
+            INDEX_TYPE_FLAT => {
+                let raw_meta = file.read(footer.meta_offset, meta_end - 1 - footer.meta_offset)?;
+                Ok((BlockMeta::decode_block_meta(&raw_meta[..]), None))
+            }
+            INDEX_TYPE_PARTITIONED => {
+                if meta_end - footer.meta_offset < PARTITIONED_INDEX_TRAILER_SIZE {
+                    bail!("corrupted SST: partitioned index too small");
+                }
+                let raw_offset = file.read(meta_end - 9, 8)?;
+                let top_level_offset = (&raw_offset[..]).get_u64();
+                if top_level_offset < footer.meta_offset || top_level_offset > meta_end - 9 - 4 {
+                    bail!("corrupted SST: invalid top-level index offset");
+                }
+                let raw_index = file.read(top_level_offset, meta_end - 9 - top_level_offset)?;
+                Ok((vec![], Some(PartitionedIndex::decode(&raw_index[..])?)))
+            }
+            index_type => bail!("unknown index type {}", index_type),
+        }
+    }
+
+    /// The footer of this SST.
+    pub fn footer(&self) -> &Footer {
+        &self.footer
+    }
+
+    /// The filters of this SST.
+    pub fn filters(&self) -> &TableFilters {
+        &self.filters
+    }
+
+    /// Check if the SST may contain keys with `prefix`, which was extracted by `extractor`.
+    pub fn may_contain_prefix(&self, extractor: &dyn PrefixExtractor, prefix: &[u8]) -> bool {
+        self.filters
+            .prefix_bloom
+            .as_ref()
+            .is_none_or(|bloom| bloom.may_contain(extractor, prefix))
+    }
+
+    /// Check if the SST may contain keys within the range, using its range filter if it has one.
+    pub fn may_contain_range(&self, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> bool {
+        self.filters
+            .range_filter
+            .as_ref()
+            .is_none_or(|filter| filter.may_contain_range(lower, upper))
+    }
+
+    /// The statistics of this SST, or `None` if it was written before properties existed.
+    pub fn properties(&self) -> Option<&TableProperties> {
+        self.properties.as_ref()
+    }
+
+    /// Get the offset and the on-disk length of a data block.
+    fn block_range(&self, block_idx: usize) -> Result<(usize, usize)> {
+        if let Some(ref index) = self.partitioned_index {
+            let partition_idx = index.find_partition_by_block(block_idx);
+            let partition = self.read_index_partition_cached(partition_idx)?;
+            let (_, mut value) =
+                partition.entry(block_idx - index.partitions[partition_idx].first_block_idx);
+            let offset = value.get_u64() as usize;
+            let len = value.get_u64() as usize;
+            return Ok((offset, len));
+        }
         let offset = self.block_metas[block_idx].offset;
         let offset_end = self
             .block_metas
             .get(block_idx + 1)
             .map_or(self.block_meta_offset, |x| x.offset);
-        let block_data = self
-            .file
-            .read(offset as u64, (offset_end - offset) as u64)?;
-        Ok(Arc::new(Block::decode(&block_data[..])))
+        Ok((offset, offset_end - offset))
+    }
+
+    /// Read a block from the disk, decompressing it if needed.
+    pub fn read_block(&self, block_idx: usize) -> Result<Arc<Block>> {
+        let (offset, len) = self.block_range(block_idx)?;
+        let mut block_data = self.file.read(offset as u64, len as u64)?;
+        if self.footer.version >= FORMAT_VERSION_V2 {
+            block_data = CompressionType::decompress_block(block_data)?;
+        }
+        Ok(Arc::new(Block::decode(block_data.into())))
     }
 
     /// Read a block from disk, with block cache.
     pub fn read_block_cached(&self, block_idx: usize) -> Result<Arc<Block>> {
         if let Some(ref block_cache) = self.block_cache {
             let blk = block_cache
-                .try_get_with((self.id, block_idx), || self.read_block(block_idx))
+                .try_get_with(BlockCacheKey::Data(self.id, block_idx), || {
+                    self.read_block(block_idx)
+                })
                 .map_err(|e| anyhow!("{}", e))?;
             Ok(blk)
         } else {
@@ -163,16 +315,205 @@ impl SsTable {
         }
     }
 
-    /// Find the block that may contain `key`.
-    pub fn find_block_idx(&self, key: &[u8]) -> usize {
-        self.block_metas
-            .partition_point(|meta| meta.first_key <= key)
-            .saturating_sub(1)
+    /// Read a partition of a partitioned index from the disk.
+    fn read_index_partition(&self, partition_idx: usize) -> Result<Arc<Block>> {
+        let index = self.partitioned_index.as_ref().unwrap();
+        let partition = &index.partitions[partition_idx];
+        let raw = self.file.read(partition.offset, partition.len)?;
+        let data = CompressionType::decompress_block(raw)?;
+        Ok(Arc::new(Block::decode(data.into())))
+    }
+
+    /// Read a partition of a partitioned index, with block cache.
+    fn read_index_partition_cached(&self, partition_idx: usize) -> Result<Arc<Block>> {
+        if let Some(ref block_cache) = self.block_cache {
+            let blk = block_cache
+                .try_get_with(
+                    BlockCacheKey::IndexPartition(self.id, partition_idx),
+                    || self.read_index_partition(partition_idx),
+                )
+                .map_err(|e| anyhow!("{}", e))?;
+            Ok(blk)
+        } else {
+            self.read_index_partition(partition_idx)
+        }
+    }
+
+    /// The order of the keys in the table.
+    pub fn comparator(&self) -> &Arc<dyn Comparator> {
+        &self.comparator
+    }
+
+    /// Find the block that may contain `key`. Reading a partition of a partitioned index may fail.
+    pub fn find_block_idx(&self, key: &[u8]) -> Result<usize> {
+        let comparator = self.comparator.as_ref();
+        if let Some(ref index) = self.partitioned_index {
+            let partition_idx = index.find_partition_by_key(key, comparator);
+            let partition = self.read_index_partition_cached(partition_idx)?;
+            let (mut low, mut high) = (0, partition.num_of_entries());
+            while low < high {
+                let mid = low + (high - low) / 2;
+                if comparator.compare(partition.entry(mid).0, key).is_le() {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+            return Ok(index.partitions[partition_idx].first_block_idx + low.saturating_sub(1));
+        }
+        Ok(self
+            .block_metas
+            .partition_point(|meta| comparator.compare(&meta.first_key, key).is_le())
+            .saturating_sub(1))
+    }
+
+    /// Point lookup of `key`, which uses the hash index of the data block if it has one. Returns
+    /// the value if the key is in the table, which is empty for a tombstone.
+    pub fn get(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        let block = self.read_block_cached(self.find_block_idx(key)?)?;
+        Ok(block
+            .find_entry(key, self.comparator.as_ref())
+            .map(|idx| block.value_bytes(idx)))
+    }
+
+    /// Point lookups of `keys`, which must be sorted by the comparator of the table, so that each
+    /// data block is read once. Returns the values in the order of `keys`.
+    pub fn multi_get(&self, keys: &[&[u8]]) -> Result<Vec<Option<Bytes>>> {
+        let mut values = Vec::with_capacity(keys.len());
+        let mut current: Option<(usize, Arc<Block>)> = None;
+        for key in keys {
+            let block_idx = self.find_block_idx(key)?;
+            let block = match current {
+                Some((idx, ref block)) if idx == block_idx => block.clone(),
+                _ => {
+                    let block = self.read_block_cached(block_idx)?;
+                    current = Some((block_idx, block.clone()));
+                    block
+                }
+            };
+            values.push(
+                block
+                    .find_entry(key, self.comparator.as_ref())
+                    .map(|idx| block.value_bytes(idx)),
+            );
+        }
+        Ok(values)
+    }
+
+    /// The first key of the table.
+    pub fn first_key(&self) -> &[u8] {
+        match self.partitioned_index {
+            Some(ref index) => &index.partitions[0].first_key,
+            None => &self.block_metas[0].first_key,
+        }
+    }
+
+    /// The last key of the table, which is read from its last data block.
+    pub fn last_key(&self) -> Result<Bytes> {
+        let block = self.read_block_cached(self.num_of_blocks() - 1)?;
+        let (key, _) = block.entry(block.num_of_entries() - 1);
+        Ok(Bytes::copy_from_slice(key))
+    }
+
+    /// The ID of the table.
+    pub fn sst_id(&self) -> usize {
+        self.id
     }
 
     /// Get number of data blocks.
     pub fn num_of_blocks(&self) -> usize {
-        self.block_metas.len()
+        match self.partitioned_index {
+            Some(ref index) => index.num_of_blocks,
+            None => self.block_metas.len(),
+        }
+    }
+
+    /// Get the top-level index if the index is partitioned.
+    pub fn partitioned_index(&self) -> Option<&PartitionedIndex> {
+        self.partitioned_index.as_ref()
+    }
+
+    /// The metas of all data blocks. All partitions of a partitioned index are read.
+    pub fn block_metas(&self) -> Result<Vec<BlockMeta>> {
+        let Some(ref index) = self.partitioned_index else {
+            return Ok(self.block_metas.clone());
+        };
+        let mut block_metas = Vec::with_capacity(index.num_of_blocks);
+        for partition_idx in 0..index.partitions.len() {
+            let partition = self.read_index_partition_cached(partition_idx)?;
+            for idx in 0..partition.num_of_entries() {
+                let (first_key, mut value) = partition.entry(idx);
+                block_metas.push(BlockMeta {
+                    offset: value.get_u64() as usize,
+                    first_key: Bytes::copy_from_slice(first_key),
+                });
+            }
+        }
+        Ok(block_metas)
+    }
+
+    /// The total on-disk size of the data blocks.
+    pub fn data_size(&self) -> u64 {
+        self.block_meta_offset as u64
+    }
+
+    /// Get the on-disk size of each data block that may contain keys within the range, with the
+    /// meta of the block. A block spans the keys from its first key to the first key of the next
+    /// block.
+    pub fn blocks_in_range(
+        &self,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+    ) -> Result<Vec<(BlockMeta, u64)>> {
+        let comparator = self.comparator.as_ref();
+        let block_metas = self.block_metas()?;
+        let mut blocks = Vec::new();
+        for (idx, meta) in block_metas.iter().enumerate() {
+            let next = block_metas.get(idx + 1);
+            let before_lower = next.is_some_and(|next| match lower {
+                Bound::Included(key) | Bound::Excluded(key) => {
+                    comparator.compare(&next.first_key, key).is_le()
+                }
+                Bound::Unbounded => false,
+            });
+            if before_lower {
+                continue;
+            }
+            let after_upper = match upper {
+                Bound::Included(key) => comparator.compare(&meta.first_key, key).is_gt(),
+                Bound::Excluded(key) => comparator.compare(&meta.first_key, key).is_ge(),
+                Bound::Unbounded => false,
+            };
+            if after_upper {
+                break;
+            }
+            let end = next.map_or(self.block_meta_offset, |next| next.offset);
+            blocks.push((meta.clone(), (end - meta.offset) as u64));
+        }
+        Ok(blocks)
+    }
+
+    /// Estimate the on-disk size of the data within the range from the index, without reading any
+    /// data block. The blocks at the edges of the range are counted in full.
+    pub fn approximate_size(&self, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> Result<u64> {
+        Ok(self
+            .blocks_in_range(lower, upper)?
+            .iter()
+            .map(|(_, size)| size)
+            .sum())
+    }
+
+    /// Estimate the number of entries within the range, assuming that the entries are spread
+    /// evenly over the data blocks. Returns 0 if the table has no properties.
+    pub fn approximate_count(&self, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> Result<u64> {
+        let Some(ref properties) = self.properties else {
+            return Ok(0);
+        };
+        if self.block_meta_offset == 0 {
+            return Ok(0);
+        }
+        let size = self.approximate_size(lower, upper)?;
+        Ok((properties.num_entries as u128 * size as u128 / self.block_meta_offset as u128) as u64)
     }
 }
 
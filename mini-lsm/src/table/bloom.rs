@@ -0,0 +1,80 @@
+use anyhow::{bail, Result};
+use bytes::{Buf, BufMut, Bytes};
+
+/// A bloom filter over 64-bit key hashes, using double hashing to derive the probes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bloom {
+    /// The bits of the filter.
+    filter: Bytes,
+    /// The number of probes per key.
+    k: u8,
+}
+
+impl Bloom {
+    /// Build a bloom filter with about `bits_per_key` bits for each of the hashes.
+    pub fn build_from_hashes(hashes: &[u64], bits_per_key: usize) -> Self {
+        // 0.69 is approximately ln(2), which minimizes the false positive rate.
+        let k = ((bits_per_key as f64 * 0.69) as u8).clamp(1, 30);
+        let nbits = (hashes.len() * bits_per_key).max(64);
+        let nbytes = nbits.div_ceil(8);
+        let nbits = nbytes * 8;
+        let mut filter = vec![0u8; nbytes];
+        for &h in hashes {
+            let (mut h, delta) = Self::split_hash(h);
+            for _ in 0..k {
+                let bit = (h as usize) % nbits;
+                filter[bit / 8] |= 1 << (bit % 8);
+                h = h.wrapping_add(delta);
+            }
+        }
+        Self {
+            filter: filter.into(),
+            k,
+        }
+    }
+
+    fn split_hash(h: u64) -> (u32, u32) {
+        (h as u32, (h >> 32) as u32 | 1)
+    }
+
+    /// Check if the hash may be in the filter. False positives are possible, false negatives are
+    /// not.
+    pub fn may_contain(&self, h: u64) -> bool {
+        let nbits = self.filter.len() * 8;
+        let (mut h, delta) = Self::split_hash(h);
+        for _ in 0..self.k {
+            let bit = (h as usize) % nbits;
+            if self.filter[bit / 8] & (1 << (bit % 8)) == 0 {
+                return false;
+            }
+            h = h.wrapping_add(delta);
+        }
+        true
+    }
+
+    /// Size of the filter in bytes.
+    pub fn size(&self) -> usize {
+        self.filter.len()
+    }
+
+    /// Encode the filter to a buffer.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        buf.put_u32(self.filter.len() as u32);
+        buf.put_slice(&self.filter);
+        buf.put_u8(self.k);
+    }
+
+    /// Decode the filter from a buffer.
+    pub fn decode(buf: &mut impl Buf) -> Result<Self> {
+        if buf.remaining() < 4 {
+            bail!("corrupted bloom filter");
+        }
+        let len = buf.get_u32() as usize;
+        if len == 0 || buf.remaining() < len + 1 {
+            bail!("corrupted bloom filter");
+        }
+        let filter = buf.copy_to_bytes(len);
+        let k = buf.get_u8();
+        Ok(Self { filter, k })
+    }
+}
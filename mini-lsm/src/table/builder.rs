@@ -2,19 +2,48 @@ use std::path::Path;
 use std::sync::Arc;
 
 use anyhow::Result;
-use bytes::BufMut;
 
-use super::{BlockMeta, FileObject, SsTable};
+use super::bloom::Bloom;
+use super::filter::{PrefixBloom, TableFilters};
+use super::range_filter::RangeFilterBuilder;
+use super::writer::TableWriter;
+use super::{
+    BlockMeta, CompressionType, Footer, PartitionedIndex, SsTable, TableCreationReason,
+    TableProperties, INDEX_TYPE_FLAT,
+};
 use crate::block::BlockBuilder;
+use crate::comparator::{self, Comparator};
+use crate::hash::key_hash;
 use crate::lsm_storage::BlockCache;
+use crate::prefix::PrefixExtractor;
+use crate::rate_limiter::{IoPriority, RateLimiter};
 
 /// Builds an SSTable from key-value pairs.
 pub struct SsTableBuilder {
     builder: BlockBuilder,
     first_key: Vec<u8>,
+    /// The data blocks not written to the file yet, which are all of them unless the builder
+    /// streams to a file.
     data: Vec<u8>,
+    /// Set if finished data blocks are streamed to the file.
+    writer: Option<TableWriter>,
+    /// The first error of streaming to the file, returned by `build`.
+    write_error: Option<anyhow::Error>,
+    /// `fsync` the file when the table is built.
+    sync: bool,
     pub(super) meta: Vec<BlockMeta>,
     block_size: usize,
+    compression: CompressionType,
+    properties: TableProperties,
+    index_partition_size: Option<usize>,
+    block_hash_index: bool,
+    prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+    /// Hashes of the distinct key prefixes, for the prefix bloom filter.
+    prefix_hashes: Vec<u64>,
+    bloom_bits_per_key: usize,
+    range_filter: Option<RangeFilterBuilder>,
+    comparator: Arc<dyn Comparator>,
+    rate_limiter: Option<(Arc<RateLimiter>, IoPriority)>,
 }
 
 impl SsTableBuilder {
@@ -22,18 +51,144 @@ impl SsTableBuilder {
     pub fn new(block_size: usize) -> Self {
         Self {
             data: Vec::new(),
+            writer: None,
+            write_error: None,
+            sync: false,
             meta: Vec::new(),
             first_key: Vec::new(),
             block_size,
             builder: BlockBuilder::new(block_size),
+            compression: CompressionType::None,
+            properties: TableProperties::default(),
+            index_partition_size: None,
+            block_hash_index: false,
+            prefix_extractor: None,
+            prefix_hashes: Vec::new(),
+            bloom_bits_per_key: 10,
+            range_filter: None,
+            comparator: comparator::bytewise(),
+            rate_limiter: None,
         }
     }
 
+    fn new_block_builder(&self) -> BlockBuilder {
+        let builder = BlockBuilder::new(self.block_size);
+        if self.block_hash_index {
+            builder.with_hash_index()
+        } else {
+            builder
+        }
+    }
+
+    /// Build a hash index in every data block for faster point lookups.
+    pub fn with_block_hash_index(mut self) -> Self {
+        assert!(self.builder.is_empty(), "must be set before adding keys");
+        self.block_hash_index = true;
+        self.builder = self.new_block_builder();
+        self
+    }
+
+    /// Set the codec used to compress data blocks.
+    pub fn with_compression(mut self, compression: CompressionType) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Split the index into partitions of about `partition_size` bytes, so that only a small
+    /// top-level index is kept in memory when the table is opened.
+    pub fn with_partitioned_index(mut self, partition_size: usize) -> Self {
+        self.index_partition_size = Some(partition_size);
+        self
+    }
+
+    /// Build a bloom filter over the key prefixes extracted by `prefix_extractor`, with about
+    /// `bits_per_key` bits per distinct prefix.
+    pub fn with_prefix_bloom(
+        mut self,
+        prefix_extractor: Arc<dyn PrefixExtractor>,
+        bits_per_key: usize,
+    ) -> Self {
+        self.prefix_extractor = Some(prefix_extractor);
+        self.bloom_bits_per_key = bits_per_key;
+        self
+    }
+
+    /// Build a range filter over the keys, which keeps `suffix_bytes` more bytes of each key
+    /// beyond the shortest prefix that tells it apart from its neighbours. The filter only works
+    /// for keys sorted by their bytes, so it is not built with other comparators.
+    pub fn with_range_filter(mut self, suffix_bytes: usize) -> Self {
+        if comparator::is_bytewise(self.comparator.as_ref()) {
+            self.range_filter = Some(RangeFilterBuilder::new(suffix_bytes));
+        }
+        self
+    }
+
+    /// Set the order of the keys, which must be added in this order.
+    pub fn with_comparator(mut self, comparator: Arc<dyn Comparator>) -> Self {
+        if !comparator::is_bytewise(comparator.as_ref()) {
+            self.range_filter = None;
+        }
+        self.comparator = comparator;
+        self
+    }
+
+    /// Write the table through a rate limiter with the priority.
+    pub fn with_rate_limiter(
+        mut self,
+        rate_limiter: Arc<RateLimiter>,
+        priority: IoPriority,
+    ) -> Self {
+        self.rate_limiter = Some((rate_limiter, priority));
+        self
+    }
+
+    /// Write finished data blocks to the file at `path` as they are built, instead of keeping the
+    /// whole table in memory until `build`, which must be given the same path.
+    pub fn with_streaming_output(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        assert!(self.meta.is_empty(), "must be set before adding keys");
+        self.writer = Some(TableWriter::create(path.as_ref())?);
+        Ok(self)
+    }
+
+    /// `fsync` the file and its directory when the table is built, so that the table survives a
+    /// crash once `build` returns.
+    pub fn with_fsync(mut self) -> Self {
+        self.sync = true;
+        self
+    }
+
+    /// Record why the table is built and which level it goes to in its properties.
+    pub fn with_creation_reason(mut self, reason: TableCreationReason, level: usize) -> Self {
+        self.properties.creation_reason = reason;
+        self.properties.level = level as u64;
+        self
+    }
+
     /// Adds a key-value pair to SSTable
     pub fn add(&mut self, key: &[u8], value: &[u8]) {
         if self.first_key.is_empty() {
             self.first_key = key.to_vec();
         }
+        self.properties.num_entries += 1;
+        if value.is_empty() {
+            self.properties.num_tombstones += 1;
+        }
+        self.properties.raw_key_bytes += key.len() as u64;
+        self.properties.raw_value_bytes += value.len() as u64;
+        if let Some(prefix) = self
+            .prefix_extractor
+            .as_ref()
+            .and_then(|extractor| extractor.extract(key))
+        {
+            // Keys are sorted, so the same prefixes are next to each other.
+            let h = key_hash(prefix);
+            if self.prefix_hashes.last() != Some(&h) {
+                self.prefix_hashes.push(h);
+            }
+        }
+        if let Some(ref mut range_filter) = self.range_filter {
+            range_filter.add(key);
+        }
 
         if self.builder.add(key, value) {
             return;
@@ -46,23 +201,41 @@ impl SsTableBuilder {
         self.first_key = key.to_vec();
     }
 
-    /// Get the estimated size of the SSTable.
+    /// Get the estimated size of the SSTable, which is the size of the finished data blocks.
     pub fn estimated_size(&self) -> usize {
-        self.data.len()
+        self.written() + self.data.len()
     }
 
+    /// Number of bytes written to the file so far.
+    fn written(&self) -> usize {
+        self.writer.as_ref().map_or(0, |writer| writer.written())
+    }
+
+    /// Finish the current data block, and write it to the file if the builder streams to one. If
+    /// the write fails, the error is kept for `build`, and the data blocks are kept in memory.
     fn finish_block(&mut self) {
-        let builder = std::mem::replace(&mut self.builder, BlockBuilder::new(self.block_size));
+        let new_builder = self.new_block_builder();
+        let builder = std::mem::replace(&mut self.builder, new_builder);
         let encoded_block = builder.build().encode();
         self.meta.push(BlockMeta {
-            offset: self.data.len(),
+            offset: self.estimated_size(),
             first_key: std::mem::take(&mut self.first_key).into(),
         });
-        self.data.extend(encoded_block);
+        self.compression
+            .compress_block(&encoded_block, &mut self.data);
+        if self.write_error.is_some() {
+            return;
+        }
+        if let Some(ref mut writer) = self.writer {
+            match writer.write(&self.data, self.rate_limiter.as_ref()) {
+                Ok(()) => self.data.clear(),
+                Err(e) => self.write_error = Some(e),
+            }
+        }
     }
 
-    /// Builds the SSTable and writes it to the given path. No need to actually write to disk until
-    /// chapter 4 block cache.
+    /// Builds the SSTable and writes it to the given path, after the data blocks written so far if
+    /// the builder streams to the file.
     pub fn build(
         mut self,
         id: usize,
@@ -70,16 +243,74 @@ impl SsTableBuilder {
         path: impl AsRef<Path>,
     ) -> Result<SsTable> {
         self.finish_block();
-        let mut buf = self.data;
-        let meta_offset = buf.len();
-        BlockMeta::encode_block_meta(&self.meta, &mut buf);
-        buf.put_u32(meta_offset as u32);
-        let file = FileObject::create(path.as_ref(), buf)?;
+        if let Some(e) = self.write_error {
+            return Err(e);
+        }
+        let mut writer = match self.writer.take() {
+            Some(writer) => {
+                assert_eq!(writer.path(), path.as_ref(), "must build the streamed file");
+                writer
+            }
+            None => TableWriter::create(path.as_ref())?,
+        };
+        // The buffer holds the rest of the file.
+        let base = writer.written();
+        let mut buf = std::mem::take(&mut self.data);
+        let meta_offset = base + buf.len();
+        let (block_metas, partitioned_index) = match self.index_partition_size {
+            Some(partition_size) => {
+                let index = PartitionedIndex::build(
+                    &self.meta,
+                    meta_offset,
+                    partition_size,
+                    self.compression,
+                    &mut buf,
+                );
+                (vec![], Some(index))
+            }
+            None => {
+                BlockMeta::encode_block_meta(&self.meta, &mut buf);
+                buf.push(INDEX_TYPE_FLAT);
+                (self.meta, None)
+            }
+        };
+        let filter_offset = base + buf.len();
+        let filters = TableFilters {
+            prefix_bloom: self.prefix_extractor.as_ref().map(|extractor| PrefixBloom {
+                extractor_name: extractor.name(),
+                bloom: Bloom::build_from_hashes(&self.prefix_hashes, self.bloom_bits_per_key),
+            }),
+            range_filter: self.range_filter.map(|builder| builder.build()),
+        };
+        filters.encode(&mut buf);
+        let properties_offset = base + buf.len();
+        if let Some(ref range_filter) = filters.range_filter {
+            let mut encoded = Vec::new();
+            range_filter.encode(&mut encoded);
+            self.properties.range_filter_bytes = encoded.len() as u64;
+            self.properties.range_filter_fp_rate_ppm = range_filter.estimate_fp_rate_ppm();
+        }
+        self.properties.compression = self.compression;
+        self.properties.creation_time = TableProperties::now();
+        self.properties.encode(&mut buf);
+        let footer = Footer::new(
+            meta_offset as u64,
+            filter_offset as u64,
+            properties_offset as u64,
+        );
+        footer.encode(&mut buf);
+        writer.write(&buf, self.rate_limiter.as_ref())?;
+        let file = writer.finish(self.sync)?;
         Ok(SsTable {
             id,
             file,
-            block_metas: self.meta,
+            block_metas,
+            partitioned_index,
             block_meta_offset: meta_offset,
+            footer,
+            filters,
+            properties: Some(self.properties),
+            comparator: self.comparator,
             block_cache,
         })
     }
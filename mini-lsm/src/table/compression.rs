@@ -0,0 +1,62 @@
+use anyhow::{anyhow, bail, Result};
+
+/// The codec used to compress a data block. The tag is stored as the last byte of every block in
+/// the SST, so tables can mix codecs and readers decompress each block transparently.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CompressionType {
+    /// Store blocks as is.
+    #[default]
+    None,
+    /// Fast LZ4 block compression.
+    Lz4,
+}
+
+impl CompressionType {
+    /// The tag of this codec on disk.
+    pub fn to_tag(self) -> u8 {
+        match self {
+            CompressionType::None => 0,
+            CompressionType::Lz4 => 1,
+        }
+    }
+
+    /// Get the codec from its tag on disk.
+    pub fn from_tag(tag: u8) -> Result<Self> {
+        match tag {
+            0 => Ok(CompressionType::None),
+            1 => Ok(CompressionType::Lz4),
+            tag => bail!("unknown compression type {}", tag),
+        }
+    }
+
+    /// Compress an encoded block and append it to `buf`, followed by the codec tag. Falls back to
+    /// no compression if it does not make the block smaller.
+    pub fn compress_block(self, block: &[u8], buf: &mut Vec<u8>) {
+        let compressed = match self {
+            CompressionType::None => None,
+            CompressionType::Lz4 => Some(lz4_flex::compress_prepend_size(block)),
+        };
+        match compressed {
+            Some(compressed) if compressed.len() < block.len() => {
+                buf.extend_from_slice(&compressed);
+                buf.push(self.to_tag());
+            }
+            _ => {
+                buf.extend_from_slice(block);
+                buf.push(CompressionType::None.to_tag());
+            }
+        }
+    }
+
+    /// Decompress a block written by `compress_block`, including the trailing codec tag. An
+    /// uncompressed block is returned in place.
+    pub fn decompress_block(mut raw: Vec<u8>) -> Result<Vec<u8>> {
+        let tag = raw.pop().ok_or_else(|| anyhow!("empty compressed block"))?;
+        let payload = raw;
+        match Self::from_tag(tag)? {
+            CompressionType::None => Ok(payload),
+            CompressionType::Lz4 => lz4_flex::decompress_size_prepended(&payload)
+                .map_err(|e| anyhow!("failed to decompress block: {}", e)),
+        }
+    }
+}
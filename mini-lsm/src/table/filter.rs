@@ -0,0 +1,103 @@
+use anyhow::{bail, Result};
+use bytes::{Buf, BufMut, Bytes};
+
+use super::bloom::Bloom;
+use super::range_filter::RangeFilter;
+use crate::hash::key_hash;
+use crate::prefix::PrefixExtractor;
+
+/// A bloom filter over the key prefixes of a table.
+const FILTER_KIND_PREFIX_BLOOM: u8 = 1;
+/// A range filter over the keys of a table.
+const FILTER_KIND_RANGE: u8 = 2;
+
+/// A bloom filter over the prefixes extracted from the keys of a table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrefixBloom {
+    /// Name of the prefix extractor that built the filter.
+    pub extractor_name: String,
+    pub bloom: Bloom,
+}
+
+impl PrefixBloom {
+    /// Check if the table may contain keys of `prefix`, which was extracted by `extractor`. A
+    /// filter built by another extractor cannot rule out anything.
+    pub fn may_contain(&self, extractor: &dyn PrefixExtractor, prefix: &[u8]) -> bool {
+        extractor.name() != self.extractor_name || self.bloom.may_contain(key_hash(prefix))
+    }
+}
+
+/// The filters of a table, stored in the filter section. Each filter is stored as its kind, its
+/// length and its content, so that readers can skip the kinds they do not know about.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TableFilters {
+    pub prefix_bloom: Option<PrefixBloom>,
+    pub range_filter: Option<RangeFilter>,
+}
+
+impl TableFilters {
+    /// Check if there is no filter.
+    pub fn is_empty(&self) -> bool {
+        self.prefix_bloom.is_none() && self.range_filter.is_none()
+    }
+
+    /// Encode the filters to a buffer.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        if let Some(ref prefix_bloom) = self.prefix_bloom {
+            let mut content = Vec::new();
+            content.put_u16(prefix_bloom.extractor_name.len() as u16);
+            content.put_slice(prefix_bloom.extractor_name.as_bytes());
+            prefix_bloom.bloom.encode(&mut content);
+            Self::put_filter(buf, FILTER_KIND_PREFIX_BLOOM, &content);
+        }
+        if let Some(ref range_filter) = self.range_filter {
+            let mut content = Vec::new();
+            range_filter.encode(&mut content);
+            Self::put_filter(buf, FILTER_KIND_RANGE, &content);
+        }
+    }
+
+    fn put_filter(buf: &mut Vec<u8>, kind: u8, content: &[u8]) {
+        buf.put_u8(kind);
+        buf.put_u32(content.len() as u32);
+        buf.put_slice(content);
+    }
+
+    /// Decode the filters from a buffer.
+    pub fn decode(mut buf: impl Buf) -> Result<Self> {
+        let mut filters = Self::default();
+        while buf.has_remaining() {
+            if buf.remaining() < 5 {
+                bail!("corrupted filter section");
+            }
+            let kind = buf.get_u8();
+            let len = buf.get_u32() as usize;
+            if buf.remaining() < len {
+                bail!("corrupted filter section");
+            }
+            let mut content: Bytes = buf.copy_to_bytes(len);
+            match kind {
+                FILTER_KIND_PREFIX_BLOOM => {
+                    if content.remaining() < 2 {
+                        bail!("corrupted prefix bloom filter");
+                    }
+                    let name_len = content.get_u16() as usize;
+                    if content.remaining() < name_len {
+                        bail!("corrupted prefix bloom filter");
+                    }
+                    let name = content.copy_to_bytes(name_len);
+                    filters.prefix_bloom = Some(PrefixBloom {
+                        extractor_name: String::from_utf8(name.to_vec())?,
+                        bloom: Bloom::decode(&mut content)?,
+                    });
+                }
+                FILTER_KIND_RANGE => {
+                    filters.range_filter = Some(RangeFilter::decode(content)?);
+                }
+                // Written by a newer version, ignore it.
+                _ => {}
+            }
+        }
+        Ok(filters)
+    }
+}
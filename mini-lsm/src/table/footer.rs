@@ -0,0 +1,123 @@
+use anyhow::{bail, Result};
+use bytes::{Buf, BufMut};
+
+use super::FileObject;
+
+/// The magic number at the very end of every SST, which is `mini-lsm` in ASCII.
+pub const SST_MAGIC: u64 = u64::from_be_bytes(*b"mini-lsm");
+
+/// SSTs written before the footer existed, which end with a bare `u32` meta offset.
+pub const FORMAT_VERSION_LEGACY: u32 = 0;
+/// The first versioned format, with a fixed-size footer.
+pub const FORMAT_VERSION_V1: u32 = 1;
+/// Every data block ends with a one-byte compression tag.
+pub const FORMAT_VERSION_V2: u32 = 2;
+/// The meta section ends with an index type byte, and the index may be partitioned.
+pub const FORMAT_VERSION_V3: u32 = 3;
+/// The format version used by `SsTableBuilder`.
+pub const CURRENT_FORMAT_VERSION: u32 = FORMAT_VERSION_V3;
+
+const SIZEOF_U32: u64 = std::mem::size_of::<u32>() as u64;
+
+/// The fixed-size footer at the end of an SST. It locates every section of the file:
+///
+/// ```text
+/// | data blocks | meta | filter | properties | meta offset (u64) | filter offset (u64) |
+/// | properties offset (u64) | version (u32) | magic (u64) |
+/// ```
+///
+/// Sections are laid out back to back, so a section ends where the next one starts, and an
+/// empty section has the same offset as the one after it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Footer {
+    /// Offset of the block meta section, which is also the end of the data blocks.
+    pub meta_offset: u64,
+    /// Offset of the filter section.
+    pub filter_offset: u64,
+    /// Offset of the properties section.
+    pub properties_offset: u64,
+    /// The format version of the SST.
+    pub version: u32,
+}
+
+impl Footer {
+    /// The size of an encoded versioned footer.
+    pub const ENCODED_SIZE: u64 = 8 * 3 + 4 + 8;
+
+    /// Create a footer of the current format version.
+    pub fn new(meta_offset: u64, filter_offset: u64, properties_offset: u64) -> Self {
+        Self {
+            meta_offset,
+            filter_offset,
+            properties_offset,
+            version: CURRENT_FORMAT_VERSION,
+        }
+    }
+
+    /// Size of the footer on disk, which depends on the format version.
+    pub fn encoded_len(&self) -> u64 {
+        if self.version == FORMAT_VERSION_LEGACY {
+            SIZEOF_U32
+        } else {
+            Self::ENCODED_SIZE
+        }
+    }
+
+    /// Encode the footer to a buffer.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        debug_assert_ne!(self.version, FORMAT_VERSION_LEGACY);
+        buf.put_u64(self.meta_offset);
+        buf.put_u64(self.filter_offset);
+        buf.put_u64(self.properties_offset);
+        buf.put_u32(self.version);
+        buf.put_u64(SST_MAGIC);
+    }
+
+    /// Read the footer at the end of a file. Files without the magic number are treated as the
+    /// legacy format, whose only footer is the `u32` meta offset.
+    pub fn read(file: &FileObject) -> Result<Self> {
+        let len = file.size();
+        if len >= Self::ENCODED_SIZE {
+            let raw = file.read(len - Self::ENCODED_SIZE, Self::ENCODED_SIZE)?;
+            let mut buf = &raw[..];
+            let meta_offset = buf.get_u64();
+            let filter_offset = buf.get_u64();
+            let properties_offset = buf.get_u64();
+            let version = buf.get_u32();
+            if buf.get_u64() == SST_MAGIC {
+                let footer = Self {
+                    meta_offset,
+                    filter_offset,
+                    properties_offset,
+                    version,
+                };
+                footer.validate(len)?;
+                return Ok(footer);
+            }
+        }
+        if len < SIZEOF_U32 {
+            bail!("file too small to be an SST: {} bytes", len);
+        }
+        let raw = file.read(len - SIZEOF_U32, SIZEOF_U32)?;
+        let meta_offset = (&raw[..]).get_u32() as u64;
+        let footer = Self {
+            meta_offset,
+            filter_offset: len - SIZEOF_U32,
+            properties_offset: len - SIZEOF_U32,
+            version: FORMAT_VERSION_LEGACY,
+        };
+        footer.validate(len)?;
+        Ok(footer)
+    }
+
+    fn validate(&self, file_len: u64) -> Result<()> {
+        if self.encoded_len() > file_len
+            || self.meta_offset > self.filter_offset
+            || self.filter_offset > self.properties_offset
+            || self.properties_offset > file_len - self.encoded_len()
+        {
+            bail!("not an SST or corrupted SST footer: {:?}", self);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::{bail, Result};
+use bytes::{Buf, BufMut, Bytes};
+
+use super::{BlockMeta, CompressionType};
+use crate::block::BlockBuilder;
+use crate::comparator::Comparator;
+
+/// The meta section is a flat list of `BlockMeta`.
+pub const INDEX_TYPE_FLAT: u8 = 0;
+/// The meta section holds index partitions and a top-level index over them.
+pub const INDEX_TYPE_PARTITIONED: u8 = 1;
+
+/// Size of the trailer of a partitioned meta section: number of data blocks (u32), offset of the
+/// top-level index (u64) and the index type (u8).
+pub(crate) const PARTITIONED_INDEX_TRAILER_SIZE: u64 = 4 + 8 + 1;
+
+/// Locates an index partition, which is a block of `first key -> (offset, length)` entries for a
+/// run of consecutive data blocks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexPartitionMeta {
+    /// Offset of the partition in the file.
+    pub offset: u64,
+    /// Length of the partition on disk.
+    pub len: u64,
+    /// Index of the first data block in this partition.
+    pub first_block_idx: usize,
+    /// The first key of the first data block in this partition.
+    pub first_key: Bytes,
+}
+
+/// A two-level index. Only the top-level index stays in memory, and the partitions are loaded
+/// through the block cache on demand.
+///
+/// ```text
+/// | partition #1 | ... | partition #N | top-level index | num of blocks (u32) |
+/// | top-level index offset (u64) | index type (u8) |
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartitionedIndex {
+    pub partitions: Vec<IndexPartitionMeta>,
+    pub num_of_blocks: usize,
+}
+
+impl PartitionedIndex {
+    /// Partition the block metas and write the meta section to `buf`. `data_end` is where the
+    /// last data block ends, which is also where `buf` ends in the file.
+    pub fn build(
+        block_metas: &[BlockMeta],
+        data_end: usize,
+        partition_size: usize,
+        compression: CompressionType,
+        buf: &mut Vec<u8>,
+    ) -> Self {
+        // The buffer may hold only the end of the file, which starts after the data blocks.
+        let base = data_end - buf.len();
+        let mut partitions = Vec::new();
+        let mut builder = BlockBuilder::new(partition_size);
+        let mut first_block_idx = 0;
+        for (idx, meta) in block_metas.iter().enumerate() {
+            let end = block_metas.get(idx + 1).map_or(data_end, |x| x.offset);
+            let mut value = Vec::with_capacity(16);
+            value.put_u64(meta.offset as u64);
+            value.put_u64((end - meta.offset) as u64);
+            if builder.add(&meta.first_key, &value) {
+                continue;
+            }
+            let full = std::mem::replace(&mut builder, BlockBuilder::new(partition_size));
+            partitions.push(Self::finish_partition(
+                full,
+                &block_metas[first_block_idx],
+                first_block_idx,
+                compression,
+                base,
+                buf,
+            ));
+            first_block_idx = idx;
+            assert!(builder.add(&meta.first_key, &value));
+        }
+        partitions.push(Self::finish_partition(
+            builder,
+            &block_metas[first_block_idx],
+            first_block_idx,
+            compression,
+            base,
+            buf,
+        ));
+
+        let top_level_offset = base + buf.len();
+        for partition in &partitions {
+            buf.put_u64(partition.offset);
+            buf.put_u64(partition.len);
+            buf.put_u32(partition.first_block_idx as u32);
+            buf.put_u16(partition.first_key.len() as u16);
+            buf.put_slice(&partition.first_key);
+        }
+        buf.put_u32(block_metas.len() as u32);
+        buf.put_u64(top_level_offset as u64);
+        buf.put_u8(INDEX_TYPE_PARTITIONED);
+        Self {
+            partitions,
+            num_of_blocks: block_metas.len(),
+        }
+    }
+
+    fn finish_partition(
+        builder: BlockBuilder,
+        first_meta: &BlockMeta,
+        first_block_idx: usize,
+        compression: CompressionType,
+        base: usize,
+        buf: &mut Vec<u8>,
+    ) -> IndexPartitionMeta {
+        let start = buf.len();
+        compression.compress_block(&builder.build().encode(), buf);
+        IndexPartitionMeta {
+            offset: (base + start) as u64,
+            len: (buf.len() - start) as u64,
+            first_block_idx,
+            first_key: first_meta.first_key.clone(),
+        }
+    }
+
+    /// Decode the top-level index followed by the number of data blocks.
+    pub fn decode(mut buf: impl Buf) -> Result<Self> {
+        let mut partitions = Vec::new();
+        while buf.remaining() > 4 {
+            if buf.remaining() < 22 {
+                bail!("corrupted top-level index");
+            }
+            let offset = buf.get_u64();
+            let len = buf.get_u64();
+            let first_block_idx = buf.get_u32() as usize;
+            let first_key_len = buf.get_u16() as usize;
+            if buf.remaining() < first_key_len {
+                bail!("corrupted top-level index");
+            }
+            let first_key = buf.copy_to_bytes(first_key_len);
+            partitions.push(IndexPartitionMeta {
+                offset,
+                len,
+                first_block_idx,
+                first_key,
+            });
+        }
+        if buf.remaining() != 4 {
+            bail!("corrupted top-level index");
+        }
+        let num_of_blocks = buf.get_u32() as usize;
+        Ok(Self {
+            partitions,
+            num_of_blocks,
+        })
+    }
+
+    /// Find the partition that may contain `key`, in the order of `comparator`.
+    pub fn find_partition_by_key(&self, key: &[u8], comparator: &dyn Comparator) -> usize {
+        self.partitions
+            .partition_point(|partition| comparator.compare(&partition.first_key, key).is_le())
+            .saturating_sub(1)
+    }
+
+    /// Find the partition that holds the block of index `block_idx`.
+    pub fn find_partition_by_block(&self, block_idx: usize) -> usize {
+        self.partitions
+            .partition_point(|partition| partition.first_block_idx <= block_idx)
+            .saturating_sub(1)
+    }
+}
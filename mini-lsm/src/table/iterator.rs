@@ -1,10 +1,11 @@
 use std::sync::Arc;
 
 use anyhow::Result;
+use bytes::Bytes;
 
 use super::SsTable;
 use crate::block::BlockIterator;
-use crate::iterators::StorageIterator;
+use crate::iterators::{SeekableIterator, StorageIterator};
 
 /// An iterator over the contents of an SSTable.
 pub struct SsTableIterator {
@@ -17,7 +18,10 @@ impl SsTableIterator {
     fn seek_to_first_inner(table: &Arc<SsTable>) -> Result<(usize, BlockIterator)> {
         Ok((
             0,
-            BlockIterator::create_and_seek_to_first(table.read_block_cached(0)?),
+            BlockIterator::create_and_seek_to_first_with_comparator(
+                table.read_block_cached(0)?,
+                table.comparator.clone(),
+            ),
         ))
     }
 
@@ -41,14 +45,19 @@ impl SsTableIterator {
     }
 
     fn seek_to_key_inner(table: &Arc<SsTable>, key: &[u8]) -> Result<(usize, BlockIterator)> {
-        let mut blk_idx = table.find_block_idx(key);
-        let mut blk_iter =
-            BlockIterator::create_and_seek_to_key(table.read_block_cached(blk_idx)?, key);
+        let mut blk_idx = table.find_block_idx(key)?;
+        let mut blk_iter = BlockIterator::create_and_seek_to_first_with_comparator(
+            table.read_block_cached(blk_idx)?,
+            table.comparator.clone(),
+        );
+        blk_iter.seek_to_key(key);
         if !blk_iter.is_valid() {
             blk_idx += 1;
             if blk_idx < table.num_of_blocks() {
-                blk_iter =
-                    BlockIterator::create_and_seek_to_first(table.read_block_cached(blk_idx)?);
+                blk_iter = BlockIterator::create_and_seek_to_first_with_comparator(
+                    table.read_block_cached(blk_idx)?,
+                    table.comparator.clone(),
+                );
             }
         }
         Ok((blk_idx, blk_iter))
@@ -83,6 +92,14 @@ impl StorageIterator for SsTableIterator {
         self.blk_iter.key()
     }
 
+    fn value_bytes(&self) -> Bytes {
+        self.blk_iter.value_bytes()
+    }
+
+    fn key_bytes(&self) -> Bytes {
+        self.blk_iter.key_bytes()
+    }
+
     fn is_valid(&self) -> bool {
         self.blk_iter.is_valid()
     }
@@ -92,11 +109,18 @@ impl StorageIterator for SsTableIterator {
         if !self.blk_iter.is_valid() {
             self.blk_idx += 1;
             if self.blk_idx < self.table.num_of_blocks() {
-                self.blk_iter = BlockIterator::create_and_seek_to_first(
+                self.blk_iter = BlockIterator::create_and_seek_to_first_with_comparator(
                     self.table.read_block_cached(self.blk_idx)?,
+                    self.table.comparator.clone(),
                 );
             }
         }
         Ok(())
     }
 }
+
+impl SeekableIterator for SsTableIterator {
+    fn seek_to_key(&mut self, key: &[u8]) -> Result<()> {
+        SsTableIterator::seek_to_key(self, key)
+    }
+}
@@ -0,0 +1,134 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Result};
+use bytes::{Buf, BufMut};
+
+use super::CompressionType;
+
+/// Why an SST was created.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TableCreationReason {
+    /// Unknown, e.g., a table built directly with `SsTableBuilder`.
+    #[default]
+    Unknown,
+    /// Flushed from a memtable.
+    Flush,
+    /// Written by a compaction.
+    Compaction,
+}
+
+impl TableCreationReason {
+    fn to_u64(self) -> u64 {
+        match self {
+            TableCreationReason::Unknown => 0,
+            TableCreationReason::Flush => 1,
+            TableCreationReason::Compaction => 2,
+        }
+    }
+
+    fn from_u64(x: u64) -> Self {
+        match x {
+            1 => TableCreationReason::Flush,
+            2 => TableCreationReason::Compaction,
+            _ => TableCreationReason::Unknown,
+        }
+    }
+}
+
+/// Statistics of an SST, written to its properties section so that a table can be inspected
+/// without scanning it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TableProperties {
+    /// Number of key-value pairs, including tombstones.
+    pub num_entries: u64,
+    /// Number of tombstones, i.e., entries with an empty value.
+    pub num_tombstones: u64,
+    /// Total size of all keys before compression.
+    pub raw_key_bytes: u64,
+    /// Total size of all values before compression.
+    pub raw_value_bytes: u64,
+    /// Creation time in seconds since the Unix epoch.
+    pub creation_time: u64,
+    /// The codec the data blocks were compressed with.
+    pub compression: CompressionType,
+    /// Why the table was created.
+    pub creation_reason: TableCreationReason,
+    /// The level the table was written to.
+    pub level: u64,
+    /// Size of the range filter in bytes, 0 if there is none.
+    pub range_filter_bytes: u64,
+    /// Estimated false positive rate of the range filter in parts per million.
+    pub range_filter_fp_rate_ppm: u64,
+}
+
+impl TableProperties {
+    /// Seconds since the Unix epoch, used as the creation time of new tables.
+    pub fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0)
+    }
+
+    fn to_pairs(&self) -> Vec<(&'static str, u64)> {
+        vec![
+            ("num_entries", self.num_entries),
+            ("num_tombstones", self.num_tombstones),
+            ("raw_key_bytes", self.raw_key_bytes),
+            ("raw_value_bytes", self.raw_value_bytes),
+            ("creation_time", self.creation_time),
+            ("compression", self.compression.to_tag() as u64),
+            ("creation_reason", self.creation_reason.to_u64()),
+            ("level", self.level),
+            ("range_filter_bytes", self.range_filter_bytes),
+            ("range_filter_fp_rate_ppm", self.range_filter_fp_rate_ppm),
+        ]
+    }
+
+    /// Encode the properties to a buffer. Each property is stored as its name followed by a `u64`
+    /// value, so that readers can skip the ones they do not know about.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let pairs = self.to_pairs();
+        buf.put_u16(pairs.len() as u16);
+        for (name, value) in pairs {
+            buf.put_u16(name.len() as u16);
+            buf.put_slice(name.as_bytes());
+            buf.put_u64(value);
+        }
+    }
+
+    /// Decode the properties from a buffer.
+    pub fn decode(mut buf: impl Buf) -> Result<Self> {
+        let mut props = Self::default();
+        if buf.remaining() < 2 {
+            bail!("corrupted table properties");
+        }
+        let num_pairs = buf.get_u16();
+        for _ in 0..num_pairs {
+            if buf.remaining() < 2 {
+                bail!("corrupted table properties");
+            }
+            let name_len = buf.get_u16() as usize;
+            if buf.remaining() < name_len + 8 {
+                bail!("corrupted table properties");
+            }
+            let name = buf.copy_to_bytes(name_len);
+            let value = buf.get_u64();
+            match &name[..] {
+                b"num_entries" => props.num_entries = value,
+                b"num_tombstones" => props.num_tombstones = value,
+                b"raw_key_bytes" => props.raw_key_bytes = value,
+                b"raw_value_bytes" => props.raw_value_bytes = value,
+                b"creation_time" => props.creation_time = value,
+                b"compression" => props.compression = CompressionType::from_tag(value as u8)?,
+                b"creation_reason" => props.creation_reason = TableCreationReason::from_u64(value),
+                b"level" => props.level = value,
+                b"range_filter_bytes" => props.range_filter_bytes = value,
+                b"range_filter_fp_rate_ppm" => props.range_filter_fp_rate_ppm = value,
+                // Written by a newer version, ignore it.
+                _ => {}
+            }
+        }
+        Ok(props)
+    }
+}
@@ -0,0 +1,265 @@
+use std::ops::Bound;
+
+use anyhow::{bail, Result};
+use bytes::{Buf, BufMut, Bytes};
+
+/// Number of truncated keys between two restart points, where a key is stored in full.
+const RESTART_INTERVAL: usize = 16;
+
+/// Size of the trailer with the number of keys and restart points.
+const TRAILER_SIZE: usize = 8;
+
+/// A SuRF-like range filter. Like SuRF-Base, it keeps the shortest prefix of each key that tells it
+/// apart from its neighbours, and like SuRF-Real, it can keep a few more real bytes of each key to
+/// lower the false positive rate.
+///
+/// A truncated key stands for all keys starting with it, unless it is the complete key. These
+/// ranges do not overlap and are in key order, so a query searches for the first range that is
+/// not below the lower bound and checks if it starts below the upper bound.
+///
+/// The filter is kept in its encoded form and searched in place. Each truncated key is stored as
+/// the length of the prefix it shares with the key before it, the length of the rest, whether it
+/// is the complete key, and the rest. Every `RESTART_INTERVAL` keys the key is stored in full, and
+/// the offsets of these restart points follow the keys, so a query binary searches the restart
+/// points and then scans a single interval. The number of keys and the number of restart points
+/// end the filter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RangeFilter {
+    data: Bytes,
+    num_keys: usize,
+    num_restarts: usize,
+    /// Offset of the restart points, which is the end of the keys.
+    restarts_offset: usize,
+}
+
+/// Builds a range filter from sorted keys.
+pub struct RangeFilterBuilder {
+    suffix_bytes: usize,
+    data: Vec<u8>,
+    restarts: Vec<u32>,
+    num_keys: usize,
+    /// The truncated key added last.
+    prev: Vec<u8>,
+    last_key: Vec<u8>,
+    /// Length of the common prefix of the last key and the key before it.
+    last_lcp: usize,
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+impl RangeFilterBuilder {
+    /// Create a builder that keeps `suffix_bytes` more bytes of each key beyond the shortest
+    /// distinguishing prefix.
+    pub fn new(suffix_bytes: usize) -> Self {
+        Self {
+            suffix_bytes,
+            data: Vec::new(),
+            restarts: Vec::new(),
+            num_keys: 0,
+            prev: Vec::new(),
+            last_key: Vec::new(),
+            last_lcp: 0,
+        }
+    }
+
+    /// Add a key, which must be greater than all keys added before.
+    pub fn add(&mut self, key: &[u8]) {
+        if self.last_key.is_empty() {
+            self.last_key = key.to_vec();
+            return;
+        }
+        debug_assert!(key > &self.last_key[..], "keys must be added in order");
+        let lcp = common_prefix_len(&self.last_key, key);
+        self.finish_last_key(self.last_lcp.max(lcp));
+        self.last_lcp = lcp;
+        self.last_key.clear();
+        self.last_key.extend_from_slice(key);
+    }
+
+    fn finish_last_key(&mut self, max_lcp: usize) {
+        let len = (max_lcp + 1 + self.suffix_bytes).min(self.last_key.len());
+        let key = &self.last_key[..len];
+        let shared = if self.num_keys.is_multiple_of(RESTART_INTERVAL) {
+            self.restarts.push(self.data.len() as u32);
+            0
+        } else {
+            common_prefix_len(&self.prev, key)
+        };
+        self.data.put_u16(shared as u16);
+        self.data.put_u16((len - shared) as u16);
+        self.data.put_u8((len == self.last_key.len()) as u8);
+        self.data.put_slice(&key[shared..]);
+        self.num_keys += 1;
+        self.prev.clear();
+        self.prev.extend_from_slice(key);
+    }
+
+    /// Build the filter.
+    pub fn build(mut self) -> RangeFilter {
+        if !self.last_key.is_empty() {
+            self.finish_last_key(self.last_lcp);
+        }
+        let restarts_offset = self.data.len();
+        for restart in self.restarts.iter() {
+            self.data.put_u32(*restart);
+        }
+        self.data.put_u32(self.num_keys as u32);
+        self.data.put_u32(self.restarts.len() as u32);
+        RangeFilter {
+            data: self.data.into(),
+            num_keys: self.num_keys,
+            num_restarts: self.restarts.len(),
+            restarts_offset,
+        }
+    }
+}
+
+/// A truncated key decoded from a filter.
+struct FilterKey {
+    key: Vec<u8>,
+    complete: bool,
+}
+
+impl FilterKey {
+    /// Check if the range of the key reaches `lower`.
+    fn reaches_lower(&self, lower: Bound<&[u8]>) -> bool {
+        let key = &self.key[..];
+        let is_prefix_of = |x: &[u8]| !self.complete && x.starts_with(key);
+        match lower {
+            Bound::Included(x) => key >= x || is_prefix_of(x),
+            Bound::Excluded(x) => key > x || is_prefix_of(x),
+            Bound::Unbounded => true,
+        }
+    }
+}
+
+impl RangeFilter {
+    fn restart(&self, idx: usize) -> usize {
+        (&self.data[self.restarts_offset + idx * 4..]).get_u32() as usize
+    }
+
+    /// Decode the key at `offset` over the key before it, which is in `key`. Returns the offset of
+    /// the next key.
+    fn read_key(&self, offset: usize, key: &mut FilterKey) -> usize {
+        let mut entry = &self.data[offset..];
+        let shared = entry.get_u16() as usize;
+        let unshared = entry.get_u16() as usize;
+        key.complete = entry.get_u8() != 0;
+        key.key.truncate(shared);
+        key.key.extend_from_slice(&entry[..unshared]);
+        offset + 5 + unshared
+    }
+
+    /// Check if the filter may contain keys within the range. False positives are possible,
+    /// false negatives are not.
+    pub fn may_contain_range(&self, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> bool {
+        let mut key = FilterKey {
+            key: Vec::new(),
+            complete: false,
+        };
+        // Find the last restart point whose key does not reach the lower bound, and then the first
+        // key that does in the interval after it.
+        let (mut low, mut high) = (0, self.num_restarts);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            self.read_key(self.restart(mid), &mut key);
+            if key.reaches_lower(lower) {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        let (mut idx, mut offset) = match low {
+            0 => (0, 0),
+            _ => ((low - 1) * RESTART_INTERVAL, self.restart(low - 1)),
+        };
+        loop {
+            if idx == self.num_keys {
+                return false;
+            }
+            offset = self.read_key(offset, &mut key);
+            if key.reaches_lower(lower) {
+                break;
+            }
+            idx += 1;
+        }
+        match upper {
+            Bound::Included(x) => &key.key[..] <= x,
+            Bound::Excluded(x) => &key.key[..] < x,
+            Bound::Unbounded => true,
+        }
+    }
+
+    /// Estimate the false positive rate, by probing the smallest key after each key, which is the
+    /// hardest query to rule out. The result is in parts per million.
+    pub fn estimate_fp_rate_ppm(&self) -> u64 {
+        if self.num_keys == 0 {
+            return 0;
+        }
+        // Only the probes after an incomplete key can hit: the probe starts with the truncated
+        // key, and the next truncated key is greater than the probe.
+        let mut key = FilterKey {
+            key: Vec::new(),
+            complete: false,
+        };
+        let (mut offset, mut incomplete) = (0, 0);
+        for _ in 0..self.num_keys {
+            offset = self.read_key(offset, &mut key);
+            incomplete += !key.complete as u64;
+        }
+        (incomplete * 1_000_000) / self.num_keys as u64
+    }
+
+    /// Encode the filter to a buffer.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        buf.put_slice(&self.data);
+    }
+
+    /// Decode the filter from a buffer, checking that every key and restart point is within it.
+    pub fn decode(data: Bytes) -> Result<Self> {
+        if data.len() < TRAILER_SIZE {
+            bail!("corrupted range filter");
+        }
+        let mut trailer = &data[data.len() - TRAILER_SIZE..];
+        let num_keys = trailer.get_u32() as usize;
+        let num_restarts = trailer.get_u32() as usize;
+        if num_restarts != num_keys.div_ceil(RESTART_INTERVAL)
+            || num_restarts * 4 > data.len() - TRAILER_SIZE
+        {
+            bail!("corrupted range filter");
+        }
+        let restarts_offset = data.len() - TRAILER_SIZE - num_restarts * 4;
+        let filter = Self {
+            data,
+            num_keys,
+            num_restarts,
+            restarts_offset,
+        };
+        let (mut offset, mut prev_len) = (0, 0);
+        for idx in 0..num_keys {
+            if idx.is_multiple_of(RESTART_INTERVAL)
+                && filter.restart(idx / RESTART_INTERVAL) != offset
+            {
+                bail!("corrupted range filter");
+            }
+            let mut entry = match filter.data[..restarts_offset].get(offset..) {
+                Some(entry) if entry.len() >= 5 => entry,
+                _ => bail!("corrupted range filter"),
+            };
+            let shared = entry.get_u16() as usize;
+            let unshared = entry.get_u16() as usize;
+            let restart = idx.is_multiple_of(RESTART_INTERVAL);
+            if (restart && shared != 0) || shared > prev_len || entry.len() < 1 + unshared {
+                bail!("corrupted range filter");
+            }
+            offset += 5 + unshared;
+            prev_len = shared + unshared;
+        }
+        if offset != restarts_offset {
+            bail!("corrupted range filter");
+        }
+        Ok(filter)
+    }
+}
@@ -1,10 +1,15 @@
+use std::ops::Bound;
 use std::sync::Arc;
 
-use bytes::Bytes;
+use bytes::{BufMut, Bytes};
 use tempfile::{tempdir, TempDir};
 
 use super::*;
 use crate::iterators::StorageIterator;
+use crate::lsm_storage::BlockCache;
+use crate::prefix::{
+    prefix_upper_bound, DelimitedPrefixExtractor, FixedPrefixExtractor, PrefixExtractor,
+};
 use crate::table::SsTableBuilder;
 
 #[test]
@@ -128,3 +133,465 @@ fn test_sst_seek_key() {
         iter.seek_to_key(b"k").unwrap();
     }
 }
+
+#[test]
+fn test_sst_footer() {
+    let (_dir, sst) = generate_sst();
+    let footer = *sst.footer();
+    assert_eq!(footer.version, CURRENT_FORMAT_VERSION);
+    assert_eq!(footer.meta_offset as usize, sst.block_meta_offset);
+    let new_sst = SsTable::open_for_test(sst.file).unwrap();
+    assert_eq!(*new_sst.footer(), footer);
+}
+
+#[test]
+fn test_sst_open_legacy_format() {
+    let (_dir, sst) = generate_sst();
+    let sst = Arc::new(sst);
+    // Rewrite the table in the legacy layout, with raw blocks and a bare `u32` meta offset.
+    let mut buf = Vec::new();
+    let mut block_metas = Vec::new();
+    for idx in 0..sst.num_of_blocks() {
+        block_metas.push(BlockMeta {
+            offset: buf.len(),
+            first_key: sst.block_metas[idx].first_key.clone(),
+        });
+        buf.extend(sst.read_block(idx).unwrap().encode());
+    }
+    let meta_offset = buf.len();
+    BlockMeta::encode_block_meta(&block_metas, &mut buf);
+    buf.put_u32(meta_offset as u32);
+    let dir = tempdir().unwrap();
+    let file = FileObject::create(&dir.path().join("2.sst"), buf).unwrap();
+    let legacy_sst = SsTable::open_for_test(file).unwrap();
+    assert_eq!(legacy_sst.footer().version, FORMAT_VERSION_LEGACY);
+    assert_eq!(legacy_sst.block_metas, block_metas);
+    let mut iter = SsTableIterator::create_and_seek_to_first(Arc::new(legacy_sst)).unwrap();
+    for i in 0..num_of_keys() {
+        assert_eq!(iter.key(), key_of(i));
+        assert_eq!(iter.value(), value_of(i));
+        iter.next().unwrap();
+    }
+    assert!(!iter.is_valid());
+}
+
+#[test]
+fn test_sst_open_foreign_file() {
+    let dir = tempdir().unwrap();
+    let file = FileObject::create(
+        &dir.path().join("foreign.sst"),
+        b"definitely not an sst file, just some text".to_vec(),
+    )
+    .unwrap();
+    assert!(SsTable::open_for_test(file).is_err());
+}
+
+#[test]
+fn test_sst_open_unknown_version() {
+    let (_dir, sst) = generate_sst();
+    let mut footer = *sst.footer();
+    footer.version = CURRENT_FORMAT_VERSION + 1;
+    let mut buf = sst.file.read(0, footer.properties_offset).unwrap();
+    footer.encode(&mut buf);
+    let dir = tempdir().unwrap();
+    let file = FileObject::create(&dir.path().join("2.sst"), buf).unwrap();
+    assert!(SsTable::open_for_test(file).is_err());
+}
+
+#[test]
+fn test_sst_compression() {
+    let mut builder = SsTableBuilder::new(128).with_compression(CompressionType::Lz4);
+    let mut uncompressed_builder = SsTableBuilder::new(128);
+    for idx in 0..num_of_keys() {
+        let value = format!("{{\"value\": \"{:0>64}\"}}", idx).into_bytes();
+        builder.add(&key_of(idx), &value);
+        uncompressed_builder.add(&key_of(idx), &value);
+    }
+    assert!(builder.estimated_size() < uncompressed_builder.estimated_size());
+    let dir = tempdir().unwrap();
+    let sst = Arc::new(builder.build_for_test(dir.path().join("1.sst")).unwrap());
+    let mut iter = SsTableIterator::create_and_seek_to_first(sst).unwrap();
+    for idx in 0..num_of_keys() {
+        let value = format!("{{\"value\": \"{:0>64}\"}}", idx).into_bytes();
+        assert_eq!(iter.key(), key_of(idx));
+        assert_eq!(iter.value(), value);
+        iter.next().unwrap();
+    }
+    assert!(!iter.is_valid());
+}
+
+#[test]
+fn test_compression_fallback_on_incompressible_block() {
+    let block = [0x42u8];
+    let mut buf = Vec::new();
+    CompressionType::Lz4.compress_block(&block, &mut buf);
+    assert_eq!(buf, [0x42, CompressionType::None.to_tag()]);
+    assert_eq!(CompressionType::decompress_block(buf).unwrap(), block);
+}
+
+#[test]
+fn test_sst_properties() {
+    let mut builder = SsTableBuilder::new(128)
+        .with_compression(CompressionType::Lz4)
+        .with_creation_reason(TableCreationReason::Flush, 0);
+    for idx in 0..num_of_keys() {
+        let value = if idx % 10 == 0 { vec![] } else { value_of(idx) };
+        builder.add(&key_of(idx), &value);
+    }
+    let dir = tempdir().unwrap();
+    let sst = builder.build_for_test(dir.path().join("1.sst")).unwrap();
+    let props = sst.properties().unwrap().clone();
+    assert_eq!(props.num_entries, num_of_keys() as u64);
+    assert_eq!(props.num_tombstones, 10);
+    assert_eq!(
+        props.raw_key_bytes,
+        (key_of(0).len() * num_of_keys()) as u64
+    );
+    assert_eq!(props.raw_value_bytes, (value_of(0).len() * 90) as u64);
+    assert_eq!(props.compression, CompressionType::Lz4);
+    assert_eq!(props.creation_reason, TableCreationReason::Flush);
+    assert_eq!(props.level, 0);
+    assert!(props.creation_time > 0);
+    let new_sst = SsTable::open_for_test(sst.file).unwrap();
+    assert_eq!(new_sst.properties(), Some(&props));
+}
+
+#[test]
+fn test_sst_partitioned_index() {
+    let mut builder = SsTableBuilder::new(128).with_partitioned_index(64);
+    for idx in 0..num_of_keys() {
+        builder.add(&key_of(idx), &value_of(idx));
+    }
+    let num_of_blocks = generate_sst().1.num_of_blocks();
+    let dir = tempdir().unwrap();
+    let block_cache = Arc::new(BlockCache::new(1024));
+    let sst = builder
+        .build(0, Some(block_cache.clone()), dir.path().join("1.sst"))
+        .unwrap();
+    assert!(sst.block_metas.is_empty());
+    assert!(sst.partitioned_index().unwrap().partitions.len() > 1);
+    assert_eq!(sst.num_of_blocks(), num_of_blocks);
+
+    let sst = SsTable::open(0, Some(block_cache.clone()), sst.file).unwrap();
+    assert_eq!(sst.num_of_blocks(), num_of_blocks);
+    let sst = Arc::new(sst);
+    let mut iter = SsTableIterator::create_and_seek_to_first(sst.clone()).unwrap();
+    for i in 0..num_of_keys() {
+        assert_eq!(iter.key(), key_of(i));
+        assert_eq!(iter.value(), value_of(i));
+        iter.next().unwrap();
+    }
+    assert!(!iter.is_valid());
+    for i in 0..num_of_keys() {
+        iter.seek_to_key(&format!("key_{:03}", i * 5 + 1).into_bytes())
+            .unwrap();
+        if i + 1 < num_of_keys() {
+            assert_eq!(iter.key(), key_of(i + 1));
+        } else {
+            assert!(!iter.is_valid());
+        }
+    }
+    iter.seek_to_key(b"k").unwrap();
+    assert_eq!(iter.key(), key_of(0));
+
+    // Block lookups read the partitions, which are cached apart from the data blocks.
+    let (_dir, expected) = generate_sst();
+    for i in 0..num_of_keys() {
+        assert_eq!(
+            sst.find_block_idx(&key_of(i)).unwrap(),
+            expected.find_block_idx(&key_of(i)).unwrap()
+        );
+    }
+    let num_of_partitions = sst.partitioned_index().unwrap().partitions.len();
+    assert_eq!(
+        block_cache.iter().count(),
+        num_of_blocks + num_of_partitions
+    );
+}
+
+#[test]
+fn test_sst_approximate_size() {
+    let (_dir, sst) = generate_sst();
+    let data_size = sst.data_size();
+    assert_eq!(
+        sst.approximate_size(Bound::Unbounded, Bound::Unbounded)
+            .unwrap(),
+        data_size
+    );
+    assert_eq!(
+        sst.approximate_count(Bound::Unbounded, Bound::Unbounded)
+            .unwrap(),
+        num_of_keys() as u64
+    );
+    // The first half of the keys, whose edge blocks are counted in full.
+    let half = sst
+        .approximate_size(
+            Bound::Unbounded,
+            Bound::Excluded(&key_of(num_of_keys() / 2)),
+        )
+        .unwrap();
+    assert!(half >= data_size / 2 && half < data_size * 2 / 3);
+    let count = sst
+        .approximate_count(Bound::Included(&key_of(10)), Bound::Included(&key_of(19)))
+        .unwrap();
+    assert!((10..20).contains(&count));
+    assert_eq!(
+        sst.approximate_size(Bound::Unbounded, Bound::Excluded(b"key"))
+            .unwrap(),
+        0
+    );
+
+    // The block metas of a partitioned index are read from its partitions.
+    let mut builder = SsTableBuilder::new(128).with_partitioned_index(64);
+    for idx in 0..num_of_keys() {
+        builder.add(&key_of(idx), &value_of(idx));
+    }
+    let dir = tempdir().unwrap();
+    let partitioned = builder.build_for_test(dir.path().join("2.sst")).unwrap();
+    assert_eq!(
+        partitioned.block_metas().unwrap(),
+        sst.block_metas().unwrap()
+    );
+    assert_eq!(partitioned.data_size(), data_size);
+}
+
+#[test]
+fn test_sst_streaming_output() {
+    let (_dir, expected) = generate_sst();
+    for partition_size in [None, Some(64)] {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("1.sst");
+        let mut builder = SsTableBuilder::new(128)
+            .with_fsync()
+            .with_streaming_output(&path)
+            .unwrap();
+        if let Some(partition_size) = partition_size {
+            builder = builder.with_partitioned_index(partition_size);
+        }
+        for idx in 0..num_of_keys() {
+            builder.add(&key_of(idx), &value_of(idx));
+        }
+        // The finished blocks are streamed out, and still count towards the size.
+        assert!(builder.estimated_size() > 0);
+        assert!(builder.meta.len() > 1);
+        // The table is written to a temporary file until it is built.
+        assert!(!path.exists());
+        assert!(crate::durable::temp_path(&path).exists());
+        let sst = builder.build_for_test(&path).unwrap();
+        assert!(!crate::durable::temp_path(&path).exists());
+        assert_eq!(sst.block_metas().unwrap(), expected.block_metas().unwrap());
+        assert_eq!(sst.data_size(), expected.data_size());
+        assert_eq!(sst.file.size(), std::fs::metadata(&path).unwrap().len());
+
+        let sst = Arc::new(SsTable::open_for_test(sst.file).unwrap());
+        let mut iter = SsTableIterator::create_and_seek_to_first(sst).unwrap();
+        for idx in 0..num_of_keys() {
+            assert_eq!(iter.key(), key_of(idx));
+            assert_eq!(iter.value(), value_of(idx));
+            iter.next().unwrap();
+        }
+        assert!(!iter.is_valid());
+    }
+
+    // A table dropped before it is built leaves no file behind.
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    let mut builder = SsTableBuilder::new(128)
+        .with_streaming_output(&path)
+        .unwrap();
+    for idx in 0..num_of_keys() {
+        builder.add(&key_of(idx), &value_of(idx));
+    }
+    drop(builder);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_sst_get() {
+    for hash_index in [false, true] {
+        let mut builder = SsTableBuilder::new(128);
+        if hash_index {
+            builder = builder.with_block_hash_index();
+        }
+        for idx in 0..num_of_keys() {
+            builder.add(&key_of(idx), &value_of(idx));
+        }
+        let dir = tempdir().unwrap();
+        let sst = builder.build_for_test(dir.path().join("1.sst")).unwrap();
+        assert_eq!(sst.read_block(0).unwrap().has_hash_index(), hash_index);
+        for idx in 0..num_of_keys() {
+            assert_eq!(sst.get(&key_of(idx)).unwrap(), Some(value_of(idx).into()));
+            assert_eq!(
+                sst.get(format!("key_{:03}", idx * 5 + 1).as_bytes())
+                    .unwrap(),
+                None
+            );
+        }
+        assert_eq!(sst.get(b"a").unwrap(), None);
+        assert_eq!(sst.get(b"z").unwrap(), None);
+    }
+}
+
+#[test]
+fn test_sst_multi_get() {
+    let (_dir, sst) = generate_sst();
+    let keys: Vec<Vec<u8>> = (0..num_of_keys())
+        .map(|idx| match idx % 3 {
+            0 => key_of(idx),
+            _ => format!("{}_missing", String::from_utf8(key_of(idx)).unwrap()).into_bytes(),
+        })
+        .collect();
+    let keys: Vec<&[u8]> = keys.iter().map(|key| &key[..]).collect();
+    let values = sst.multi_get(&keys).unwrap();
+    for (idx, value) in values.iter().enumerate() {
+        match idx % 3 {
+            0 => assert_eq!(value.as_deref(), Some(&value_of(idx)[..])),
+            _ => assert!(value.is_none()),
+        }
+    }
+}
+
+#[test]
+fn test_sst_prefix_bloom() {
+    let extractor = Arc::new(DelimitedPrefixExtractor::new(b'/', 2));
+    let mut builder = SsTableBuilder::new(128).with_prefix_bloom(extractor.clone(), 10);
+    for tenant in 0..10 {
+        for id in 0..10 {
+            let key = format!("tenant{}/entity/{}", tenant * 2, id);
+            builder.add(key.as_bytes(), &value_of(id));
+        }
+    }
+    let dir = tempdir().unwrap();
+    let sst = builder.build_for_test(dir.path().join("1.sst")).unwrap();
+    let sst = SsTable::open_for_test(sst.file).unwrap();
+    assert_eq!(
+        sst.filters().prefix_bloom.as_ref().unwrap().extractor_name,
+        extractor.name()
+    );
+    for tenant in 0..10 {
+        let prefix = format!("tenant{}/entity/", tenant * 2);
+        assert!(sst.may_contain_prefix(extractor.as_ref(), prefix.as_bytes()));
+    }
+    let false_positives = (0..1000)
+        .filter(|tenant| {
+            let prefix = format!("tenant{}/entity/", tenant * 2 + 1);
+            sst.may_contain_prefix(extractor.as_ref(), prefix.as_bytes())
+        })
+        .count();
+    assert!(false_positives < 100, "too many false positives");
+    // A filter built by another extractor cannot rule out anything.
+    let other = FixedPrefixExtractor::new(7);
+    assert!(sst.may_contain_prefix(&other, b"tenant1"));
+
+    // A truncated filter fails to decode.
+    let mut buf = Vec::new();
+    sst.filters().encode(&mut buf);
+    let content = &buf[5..];
+    for len in 0..content.len() {
+        let mut section = vec![buf[0]];
+        section.put_u32(len as u32);
+        section.put_slice(&content[..len]);
+        assert!(TableFilters::decode(&section[..]).is_err());
+    }
+}
+
+#[test]
+fn test_range_filter() {
+    let keys: &[&[u8]] = &[b"apple", b"apricot", b"banana", b"band", b"cherry"];
+    let mut builder = RangeFilterBuilder::new(0);
+    for key in keys {
+        builder.add(key);
+    }
+    let filter = builder.build();
+    let may_contain =
+        |lower: Bound<&[u8]>, upper: Bound<&[u8]>| filter.may_contain_range(lower, upper);
+    for key in keys {
+        assert!(may_contain(Bound::Included(key), Bound::Included(key)));
+    }
+    assert!(may_contain(Bound::Unbounded, Bound::Unbounded));
+    assert!(may_contain(Bound::Included(b"b"), Bound::Excluded(b"c")));
+    assert!(!may_contain(Bound::Excluded(b"d"), Bound::Unbounded));
+    assert!(!may_contain(Bound::Unbounded, Bound::Excluded(b"app")));
+    assert!(!may_contain(Bound::Included(b"ar"), Bound::Included(b"az")));
+    assert!(!may_contain(Bound::Included(b"d"), Bound::Unbounded));
+    assert!(!may_contain(Bound::Included(b"bb"), Bound::Included(b"bz")));
+    // "apple" is truncated to "app", so ranges within "app" cannot be ruled out.
+    assert!(may_contain(
+        Bound::Included(b"appz"),
+        Bound::Included(b"appzz")
+    ));
+    let mut buf = Vec::new();
+    filter.encode(&mut buf);
+    assert_eq!(RangeFilter::decode(buf.clone().into()).unwrap(), filter);
+
+    // Keeping more bytes of each key rules out more ranges.
+    let mut builder = RangeFilterBuilder::new(8);
+    for key in keys {
+        builder.add(key);
+    }
+    let filter = builder.build();
+    assert!(!filter.may_contain_range(Bound::Included(b"appz"), Bound::Included(b"appzz")));
+    assert!(!filter.may_contain_range(Bound::Excluded(b"cherry"), Bound::Unbounded));
+    assert_eq!(filter.estimate_fp_rate_ppm(), 0);
+
+    // Keys spanning several restart points.
+    let mut builder = RangeFilterBuilder::new(1);
+    for idx in 0..100 {
+        builder.add(&key_of(idx * 2));
+    }
+    let filter = builder.build();
+    for idx in 0..100 {
+        let key = key_of(idx * 2);
+        assert!(filter.may_contain_range(Bound::Included(&key), Bound::Included(&key)));
+        let next = key_of(idx * 2 + 1);
+        assert!(!filter.may_contain_range(Bound::Included(&next), Bound::Included(&next)));
+    }
+
+    // A truncated filter fails to decode.
+    let mut buf = Vec::new();
+    filter.encode(&mut buf);
+    for len in 0..buf.len() {
+        assert!(RangeFilter::decode(Bytes::copy_from_slice(&buf[..len])).is_err());
+    }
+}
+
+#[test]
+fn test_sst_range_filter() {
+    let mut builder = SsTableBuilder::new(128).with_range_filter(1);
+    for idx in 0..100 {
+        builder.add(&key_of(idx * 2), &value_of(idx * 2));
+    }
+    let dir = tempdir().unwrap();
+    let sst = builder.build_for_test(dir.path().join("1.sst")).unwrap();
+    let sst = SsTable::open_for_test(sst.file).unwrap();
+    let properties = sst.properties().unwrap();
+    assert!(properties.range_filter_bytes > 0);
+    assert!(properties.range_filter_fp_rate_ppm <= 1_000_000);
+    for idx in 0..100 {
+        let key = key_of(idx * 2);
+        assert!(sst.may_contain_range(Bound::Included(&key), Bound::Included(&key)));
+        let next = key_of(idx * 2 + 1);
+        assert!(!sst.may_contain_range(Bound::Included(&next), Bound::Included(&next)));
+    }
+    assert!(!sst.may_contain_range(Bound::Excluded(&key_of(198)), Bound::Unbounded));
+    assert!(!sst.may_contain_range(Bound::Unbounded, Bound::Excluded(&key_of(0))));
+
+    // Without a filter nothing can be ruled out.
+    let (_dir, sst) = generate_sst();
+    assert!(sst.may_contain_range(Bound::Included(b"x"), Bound::Included(b"y")));
+    assert_eq!(sst.properties().unwrap().range_filter_bytes, 0);
+}
+
+#[test]
+fn test_prefix_extractor() {
+    let delimited = DelimitedPrefixExtractor::new(b'/', 2);
+    assert_eq!(delimited.extract(b"a/b/c"), Some(&b"a/b/"[..]));
+    assert_eq!(delimited.extract(b"a/b/"), Some(&b"a/b/"[..]));
+    assert_eq!(delimited.extract(b"a/b"), None);
+    let fixed = FixedPrefixExtractor::new(2);
+    assert_eq!(fixed.extract(b"abc"), Some(&b"ab"[..]));
+    assert_eq!(fixed.extract(b"a"), None);
+    assert_eq!(prefix_upper_bound(b"ab"), Bound::Excluded(b"ac".to_vec()));
+    assert_eq!(prefix_upper_bound(b"a\xff"), Bound::Excluded(b"b".to_vec()));
+    assert_eq!(prefix_upper_bound(b"\xff\xff"), Bound::Unbounded);
+}
@@ -0,0 +1,97 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use super::FileObject;
+use crate::durable;
+use crate::rate_limiter::{IoPriority, RateLimiter};
+
+/// The size of the chunks written through a rate limiter.
+const RATE_LIMITED_CHUNK_SIZE: usize = 64 << 10;
+
+/// Writes an SST file through a buffer as it is built. The file is written to a temporary name,
+/// and renamed into place when it is finished. The temporary file is removed if the writer is
+/// dropped before that.
+pub(super) struct TableWriter {
+    writer: Option<BufWriter<File>>,
+    path: PathBuf,
+    temp_path: PathBuf,
+    /// Number of bytes written so far.
+    written: usize,
+    /// Whether the file has been renamed into place.
+    installed: bool,
+}
+
+impl TableWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let temp_path = durable::temp_path(path);
+        Ok(Self {
+            writer: Some(BufWriter::new(File::create(&temp_path)?)),
+            path: path.to_path_buf(),
+            temp_path,
+            written: 0,
+            installed: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Append data to the file, in chunks that go through the rate limiter first if there is one.
+    pub fn write(
+        &mut self,
+        data: &[u8],
+        rate_limiter: Option<&(std::sync::Arc<RateLimiter>, IoPriority)>,
+    ) -> Result<()> {
+        let writer = self.writer.as_mut().unwrap();
+        match rate_limiter {
+            Some((rate_limiter, priority)) => {
+                for chunk in data.chunks(RATE_LIMITED_CHUNK_SIZE) {
+                    rate_limiter.request(chunk.len(), *priority);
+                    writer.write_all(chunk)?;
+                }
+            }
+            None => writer.write_all(data)?,
+        }
+        self.written += data.len();
+        Ok(())
+    }
+
+    /// Flush the buffer and rename the file into place. If `sync` is set, the file is `fsync`ed
+    /// before the rename and the directory after it. Returns the file opened for reads.
+    pub fn finish(mut self, sync: bool) -> Result<FileObject> {
+        let file = self.writer.take().unwrap().into_inner()?;
+        if sync {
+            file.sync_all()?;
+        }
+        drop(file);
+        if sync {
+            durable::install(&self.temp_path, &self.path)?;
+        } else {
+            std::fs::rename(&self.temp_path, &self.path)?;
+        }
+        self.installed = true;
+        Ok(FileObject(
+            File::options().read(true).write(false).open(&self.path)?,
+            self.written as u64,
+        ))
+    }
+}
+
+impl Drop for TableWriter {
+    fn drop(&mut self) {
+        if !self.installed {
+            // Close the file first. The file is only a leftover, so a failure to remove it is
+            // ignored, and the next open removes it.
+            drop(self.writer.take());
+            let _ = std::fs::remove_file(&self.temp_path);
+        }
+    }
+}
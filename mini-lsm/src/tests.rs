@@ -1 +1,2 @@
 pub mod day4_tests;
+pub mod storage_tests;
@@ -0,0 +1,944 @@
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use super::day4_tests::check_iter_result;
+use crate::comparator::{ReverseBytewiseComparator, U64LittleEndianComparator};
+use crate::durable::TEMP_SUFFIX;
+use crate::iterators::{SeekableIterator, StorageIterator};
+use crate::lsm_storage::{
+    LsmStorage, LsmStorageOptions, WriteStallOptions, WriteStallState, WriteStallStats,
+};
+use crate::prefix::DelimitedPrefixExtractor;
+use crate::rate_limiter::RateLimiter;
+use crate::scan::{ContinuationToken, ScanOptions};
+
+#[test]
+fn test_storage_get_with_block_hash_index() {
+    let dir = tempdir().unwrap();
+    let storage = LsmStorage::open_with_options(
+        &dir,
+        LsmStorageOptions {
+            block_hash_index: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    storage.put(b"1", b"233").unwrap();
+    storage.put(b"2", b"2333").unwrap();
+    storage.put(b"4", b"233333").unwrap();
+    storage.sync().unwrap();
+    storage.delete(b"2").unwrap();
+    storage.sync().unwrap();
+    assert_eq!(&storage.get(b"1").unwrap().unwrap()[..], b"233");
+    assert!(storage.get(b"2").unwrap().is_none());
+    assert!(storage.get(b"3").unwrap().is_none());
+    assert_eq!(&storage.get(b"4").unwrap().unwrap()[..], b"233333");
+    assert!(storage.get(b"5").unwrap().is_none());
+}
+
+#[test]
+fn test_storage_prefix_scan() {
+    let dir = tempdir().unwrap();
+    let storage = LsmStorage::open_with_options(
+        &dir,
+        LsmStorageOptions {
+            prefix_extractor: Some(Arc::new(DelimitedPrefixExtractor::new(b'/', 1))),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    storage.put(b"a/1", b"1").unwrap();
+    storage.put(b"b/1", b"1").unwrap();
+    storage.sync().unwrap();
+    storage.put(b"b/2", b"2").unwrap();
+    storage.put(b"b0", b"3").unwrap();
+    storage.put(b"c/1", b"1").unwrap();
+    storage.sync().unwrap();
+    storage.put(b"b/3", b"3").unwrap();
+    storage.delete(b"b/1").unwrap();
+    check_iter_result(
+        storage.prefix_scan(b"b/").unwrap(),
+        vec![
+            (Bytes::from("b/2"), Bytes::from("2")),
+            (Bytes::from("b/3"), Bytes::from("3")),
+        ],
+    );
+    check_iter_result(
+        storage.prefix_scan(b"a/").unwrap(),
+        vec![(Bytes::from("a/1"), Bytes::from("1"))],
+    );
+    check_iter_result(storage.prefix_scan(b"d/").unwrap(), vec![]);
+    check_iter_result(
+        storage.prefix_scan(b"b").unwrap(),
+        vec![
+            (Bytes::from("b/2"), Bytes::from("2")),
+            (Bytes::from("b/3"), Bytes::from("3")),
+            (Bytes::from("b0"), Bytes::from("3")),
+        ],
+    );
+    assert_eq!(&storage.get(b"c/1").unwrap().unwrap()[..], b"1");
+    assert!(storage.get(b"d/1").unwrap().is_none());
+}
+
+#[test]
+fn test_storage_scan_with_range_filter() {
+    let dir = tempdir().unwrap();
+    let storage = LsmStorage::open_with_options(
+        &dir,
+        LsmStorageOptions {
+            range_filter_suffix_bytes: Some(0),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    storage.put(b"apple", b"1").unwrap();
+    storage.put(b"apricot", b"2").unwrap();
+    storage.sync().unwrap();
+    storage.put(b"banana", b"3").unwrap();
+    storage.put(b"band", b"4").unwrap();
+    storage.sync().unwrap();
+    let tables = storage.sstables_for_test();
+    assert_eq!(tables.len(), 2);
+    for table in tables {
+        let filter = table.filters().range_filter.as_ref().unwrap();
+        let mut encoded = Vec::new();
+        filter.encode(&mut encoded);
+        let properties = table.properties().unwrap();
+        assert_eq!(properties.range_filter_bytes, encoded.len() as u64);
+        assert_eq!(
+            properties.range_filter_fp_rate_ppm,
+            filter.estimate_fp_rate_ppm()
+        );
+    }
+    // The gap between "apple" and "apricot" lies within the keys of the first table, and its
+    // range filter skips the table without reading a block.
+    let num_cached_blocks = storage.num_cached_blocks_for_test();
+    check_iter_result(
+        storage
+            .scan(Bound::Included(b"apq"), Bound::Excluded(b"apr"))
+            .unwrap(),
+        vec![],
+    );
+    assert_eq!(storage.num_cached_blocks_for_test(), num_cached_blocks);
+    storage.delete(b"apricot").unwrap();
+    check_iter_result(
+        storage
+            .scan(Bound::Included(b"ap"), Bound::Excluded(b"b"))
+            .unwrap(),
+        vec![(Bytes::from("apple"), Bytes::from("1"))],
+    );
+    check_iter_result(
+        storage
+            .scan(Bound::Included(b"ban"), Bound::Unbounded)
+            .unwrap(),
+        vec![
+            (Bytes::from("banana"), Bytes::from("3")),
+            (Bytes::from("band"), Bytes::from("4")),
+        ],
+    );
+    check_iter_result(
+        storage
+            .scan(Bound::Included(b"c"), Bound::Unbounded)
+            .unwrap(),
+        vec![],
+    );
+}
+
+#[test]
+fn test_storage_with_comparator() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions {
+        comparator: Arc::new(U64LittleEndianComparator),
+        index_partition_size: Some(64),
+        block_size: 64,
+        ..Default::default()
+    };
+    let storage = LsmStorage::open_with_options(&dir, options.clone()).unwrap();
+    let key = |x: u64| Bytes::copy_from_slice(&x.to_le_bytes());
+    for x in (0..300).step_by(3) {
+        storage.put(&key(x), b"1").unwrap();
+    }
+    storage.sync().unwrap();
+    for x in (1..300).step_by(3) {
+        storage.put(&key(x), b"2").unwrap();
+    }
+    storage.delete(&key(3)).unwrap();
+    storage.sync().unwrap();
+    storage.put(&key(256), b"3").unwrap();
+    assert_eq!(&storage.get(&key(255)).unwrap().unwrap()[..], b"1");
+    assert_eq!(&storage.get(&key(256)).unwrap().unwrap()[..], b"3");
+    assert!(storage.get(&key(3)).unwrap().is_none());
+    assert!(storage.get(&key(257)).unwrap().is_none());
+    check_iter_result(
+        storage
+            .scan(Bound::Excluded(&key(0)), Bound::Included(&key(258)))
+            .unwrap(),
+        (1..=258)
+            .filter(|x| x % 3 != 2 && *x != 3)
+            .map(|x| {
+                let value = match x {
+                    256 => "3",
+                    x if x % 3 == 0 => "1",
+                    _ => "2",
+                };
+                (key(x), Bytes::from(value))
+            })
+            .collect(),
+    );
+    drop(storage);
+    assert!(LsmStorage::open_with_options(&dir, options).is_ok());
+    assert!(LsmStorage::open(&dir).is_err());
+    assert!(LsmStorage::open_with_options(
+        &dir,
+        LsmStorageOptions {
+            comparator: Arc::new(ReverseBytewiseComparator),
+            ..Default::default()
+        },
+    )
+    .is_err());
+}
+
+#[test]
+fn test_storage_scan_reverse_order() {
+    let dir = tempdir().unwrap();
+    let storage = LsmStorage::open_with_options(
+        &dir,
+        LsmStorageOptions {
+            comparator: Arc::new(ReverseBytewiseComparator),
+            range_filter_suffix_bytes: Some(0),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    storage.put(b"1", b"1").unwrap();
+    storage.put(b"3", b"3").unwrap();
+    storage.sync().unwrap();
+    storage.put(b"2", b"2").unwrap();
+    storage.put(b"4", b"4").unwrap();
+    check_iter_result(
+        storage
+            .scan(Bound::Included(b"3"), Bound::Excluded(b"1"))
+            .unwrap(),
+        vec![
+            (Bytes::from("3"), Bytes::from("3")),
+            (Bytes::from("2"), Bytes::from("2")),
+        ],
+    );
+    assert!(storage.prefix_scan(b"1").is_err());
+}
+
+#[test]
+fn test_storage_multi_get() {
+    let dir = tempdir().unwrap();
+    let storage = LsmStorage::open_with_options(
+        &dir,
+        LsmStorageOptions {
+            block_size: 64,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    for idx in 0..100 {
+        storage
+            .put(format!("key_{:03}", idx).as_bytes(), b"sst1")
+            .unwrap();
+    }
+    storage.sync().unwrap();
+    for idx in (0..100).step_by(2) {
+        storage
+            .put(format!("key_{:03}", idx).as_bytes(), b"sst2")
+            .unwrap();
+    }
+    storage.delete(b"key_003").unwrap();
+    storage.sync().unwrap();
+    storage.put(b"key_004", b"memtable").unwrap();
+    storage.delete(b"key_005").unwrap();
+    let keys: [&[u8]; 8] = [
+        b"key_099", b"key_004", b"key_003", b"key_100", b"key_005", b"key_001", b"key_002",
+        b"key_004",
+    ];
+    let values = storage.multi_get(&keys).unwrap();
+    for (key, value) in keys.iter().zip(values) {
+        assert_eq!(value, storage.get(key).unwrap());
+    }
+    assert_eq!(
+        storage.multi_get(&keys[..2]).unwrap(),
+        vec![Some(Bytes::from("sst1")), Some(Bytes::from("memtable"))]
+    );
+    assert!(storage.multi_get(&[]).unwrap().is_empty());
+}
+
+#[test]
+fn test_storage_conditional_writes() {
+    let dir = tempdir().unwrap();
+    let storage = LsmStorage::open(&dir).unwrap();
+    assert!(storage.put_if_absent(b"lease", b"owner1").unwrap());
+    assert!(!storage.put_if_absent(b"lease", b"owner2").unwrap());
+    storage.sync().unwrap();
+    assert!(!storage
+        .compare_and_swap(b"lease", Some(b"owner2"), b"owner3")
+        .unwrap());
+    assert!(!storage.compare_and_swap(b"lease", None, b"owner3").unwrap());
+    assert!(storage
+        .compare_and_swap(b"lease", Some(b"owner1"), b"owner2")
+        .unwrap());
+    assert_eq!(&storage.get(b"lease").unwrap().unwrap()[..], b"owner2");
+    assert!(!storage.delete_if_equals(b"lease", b"owner1").unwrap());
+    assert!(storage.delete_if_equals(b"lease", b"owner2").unwrap());
+    assert!(storage.get(b"lease").unwrap().is_none());
+    assert!(!storage.delete_if_equals(b"lease", b"owner2").unwrap());
+    assert!(storage.compare_and_swap(b"lease", None, b"owner3").unwrap());
+    assert_eq!(&storage.get(b"lease").unwrap().unwrap()[..], b"owner3");
+}
+
+#[test]
+fn test_storage_compare_and_swap_concurrent() {
+    let dir = tempdir().unwrap();
+    let storage = LsmStorage::open(&dir).unwrap();
+    storage.put(b"counter", &0u64.to_be_bytes()).unwrap();
+    std::thread::scope(|scope| {
+        for thread in 0..4 {
+            let storage = &storage;
+            scope.spawn(move || {
+                for idx in 0..100 {
+                    loop {
+                        let current = storage.get(b"counter").unwrap().unwrap();
+                        let next = u64::from_be_bytes(current[..].try_into().unwrap()) + 1;
+                        if storage
+                            .compare_and_swap(b"counter", Some(&current), &next.to_be_bytes())
+                            .unwrap()
+                        {
+                            break;
+                        }
+                    }
+                    if thread == 0 && idx % 20 == 0 {
+                        storage.sync().unwrap();
+                    }
+                }
+            });
+        }
+    });
+    let counter = storage.get(b"counter").unwrap().unwrap();
+    assert_eq!(u64::from_be_bytes(counter[..].try_into().unwrap()), 400);
+}
+
+#[test]
+fn test_storage_scan_seek() {
+    let dir = tempdir().unwrap();
+    let storage = LsmStorage::open(&dir).unwrap();
+    for idx in 0..10 {
+        storage.put(format!("{}", idx).as_bytes(), b"sst").unwrap();
+    }
+    storage.sync().unwrap();
+    storage.delete(b"4").unwrap();
+    storage.put(b"5", b"memtable").unwrap();
+    let mut iter = storage
+        .scan(Bound::Included(b"2"), Bound::Excluded(b"7"))
+        .unwrap();
+    iter.seek_to_key(b"4").unwrap();
+    assert_eq!(iter.key(), b"5");
+    assert_eq!(iter.value(), b"memtable");
+    iter.seek_to_key(b"7").unwrap();
+    assert!(!iter.is_valid());
+    iter.seek_to_key(b"3").unwrap();
+    check_iter_result(
+        iter,
+        vec![
+            (Bytes::from("3"), Bytes::from("sst")),
+            (Bytes::from("5"), Bytes::from("memtable")),
+            (Bytes::from("6"), Bytes::from("sst")),
+        ],
+    );
+
+    // Seeking below the lower bound stays within the range of the scan.
+    storage.put(b"1", b"memtable").unwrap();
+    let mut iter = storage
+        .scan(Bound::Excluded(b"2"), Bound::Excluded(b"5"))
+        .unwrap();
+    iter.seek_to_key(b"0").unwrap();
+    check_iter_result(iter, vec![(Bytes::from("3"), Bytes::from("sst"))]);
+    let mut iter = storage.prefix_scan(b"3").unwrap();
+    iter.seek_to_key(b"1").unwrap();
+    check_iter_result(iter, vec![(Bytes::from("3"), Bytes::from("sst"))]);
+}
+
+#[test]
+fn test_storage_scan_refresh() {
+    let dir = tempdir().unwrap();
+    let storage = LsmStorage::open(&dir).unwrap();
+    storage.put(b"1", b"1").unwrap();
+    storage.put(b"3", b"3").unwrap();
+    storage.put(b"5", b"5").unwrap();
+    let mut iter = storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap();
+    assert_eq!(iter.key(), b"1");
+    storage.sync().unwrap();
+    storage.put(b"2", b"2").unwrap();
+    storage.delete(b"3").unwrap();
+    storage.put(b"4", b"4").unwrap();
+    storage.put(b"5", b"5.new").unwrap();
+    iter.next().unwrap();
+    // The iterator reads from its snapshot until it is refreshed.
+    assert_eq!(iter.key(), b"3");
+    iter.refresh().unwrap();
+    check_iter_result(
+        iter,
+        vec![
+            (Bytes::from("4"), Bytes::from("4")),
+            (Bytes::from("5"), Bytes::from("5.new")),
+        ],
+    );
+}
+
+#[test]
+fn test_storage_scan_entries() {
+    let dir = tempdir().unwrap();
+    let storage = LsmStorage::open(&dir).unwrap();
+    for idx in 0..5 {
+        storage
+            .put(
+                format!("key_{}", idx).as_bytes(),
+                format!("{}", idx).as_bytes(),
+            )
+            .unwrap();
+    }
+    storage.sync().unwrap();
+    storage.delete(b"key_1").unwrap();
+    storage.put(b"key_3", b"30").unwrap();
+    let values: Vec<u32> = storage
+        .scan(Bound::Unbounded, Bound::Unbounded)
+        .unwrap()
+        .into_iter()
+        .map(|entry| {
+            let (_, value) = entry.unwrap();
+            std::str::from_utf8(&value).unwrap().parse().unwrap()
+        })
+        .filter(|value| value % 2 == 0)
+        .collect();
+    assert_eq!(values, vec![0, 2, 30, 4]);
+
+    // Entries from memtables share the buffer of the memtable.
+    let (_, value) = storage
+        .scan(Bound::Included(b"key_3"), Bound::Unbounded)
+        .unwrap()
+        .into_iter()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        value.as_ptr(),
+        storage.get(b"key_3").unwrap().unwrap().as_ptr()
+    );
+
+    assert_eq!(
+        storage
+            .scan_collect(b"key_1".as_slice()..b"key_4".as_slice(), 10)
+            .unwrap(),
+        vec![
+            (Bytes::from("key_2"), Bytes::from("2")),
+            (Bytes::from("key_3"), Bytes::from("30")),
+        ]
+    );
+    assert_eq!(storage.scan_collect(.., 2).unwrap().len(), 2);
+    assert!(storage.scan_collect(.., 0).unwrap().is_empty());
+}
+
+#[test]
+fn test_storage_scan_pages() {
+    let dir = tempdir().unwrap();
+    let storage = LsmStorage::open(&dir).unwrap();
+    for idx in 0..10 {
+        storage
+            .put(format!("key_{}", idx).as_bytes(), b"value")
+            .unwrap();
+    }
+    storage.sync().unwrap();
+    storage.delete(b"key_5").unwrap();
+
+    // Page through the keys, passing the token around as bytes.
+    let mut options = ScanOptions {
+        limit: Some(3),
+        ..Default::default()
+    };
+    let mut keys = Vec::new();
+    loop {
+        let page = storage
+            .scan_with_options(Bound::Excluded(b"key_0"), Bound::Unbounded, &options)
+            .unwrap();
+        assert!(page.entries.len() <= 3);
+        keys.extend(page.entries.into_iter().map(|(key, _)| key));
+        let Some(token) = page.continuation else {
+            break;
+        };
+        // A key inserted before the position of the token is not seen.
+        storage.put(b"key_0a", b"value").unwrap();
+        options.continuation = Some(ContinuationToken::from_bytes(&token.to_bytes()).unwrap());
+    }
+    let expected: Vec<Bytes> = [1, 2, 3, 4, 6, 7, 8, 9]
+        .iter()
+        .map(|idx| Bytes::from(format!("key_{}", idx)))
+        .collect();
+    assert_eq!(keys, expected);
+
+    // Each entry is 10 bytes, and a page holds at least one entry.
+    let options = ScanOptions {
+        max_bytes: Some(25),
+        ..Default::default()
+    };
+    let page = storage
+        .scan_with_options(Bound::Unbounded, Bound::Excluded(b"key_3"), &options)
+        .unwrap();
+    assert_eq!(page.entries.len(), 2);
+    let options = ScanOptions {
+        max_bytes: Some(1),
+        continuation: page.continuation,
+        ..Default::default()
+    };
+    let page = storage
+        .scan_with_options(Bound::Unbounded, Bound::Excluded(b"key_3"), &options)
+        .unwrap();
+    assert_eq!(
+        page.entries,
+        vec![(Bytes::from("key_1"), Bytes::from("value"))]
+    );
+    assert!(page.continuation.is_some());
+
+    let options = ScanOptions {
+        key_only: true,
+        ..Default::default()
+    };
+    let page = storage
+        .scan_with_options(Bound::Included(b"key_8"), Bound::Unbounded, &options)
+        .unwrap();
+    assert_eq!(
+        page.entries,
+        vec![
+            (Bytes::from("key_8"), Bytes::new()),
+            (Bytes::from("key_9"), Bytes::new()),
+        ]
+    );
+    assert!(page.continuation.is_none());
+
+    // A page without entries would never advance.
+    let options = ScanOptions {
+        limit: Some(0),
+        ..Default::default()
+    };
+    assert!(storage
+        .scan_with_options(Bound::Included(b"key_8"), Bound::Unbounded, &options)
+        .is_err());
+
+    assert!(ContinuationToken::from_bytes(b"").is_err());
+    assert!(ContinuationToken::from_bytes(b"\x03key").is_err());
+    assert!(ContinuationToken::from_bytes(b"\x01").is_err());
+}
+
+#[test]
+fn test_storage_approximate_size() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions {
+        block_size: 256,
+        ..Default::default()
+    };
+    let storage = LsmStorage::open_with_options(&dir, options).unwrap();
+    for idx in 0..1000 {
+        storage
+            .put(format!("key_{:04}", idx).as_bytes(), b"value")
+            .unwrap();
+    }
+    assert_eq!(storage.approximate_size(.., false).unwrap(), 0);
+    assert_eq!(storage.approximate_size(.., true).unwrap(), 1000 * 13);
+    assert_eq!(
+        storage
+            .approximate_count(&b"key_0100"[..]..&b"key_0200"[..], true)
+            .unwrap(),
+        100
+    );
+    storage.sync().unwrap();
+
+    let total = storage.approximate_size(.., false).unwrap();
+    assert!(total > 1000 * 13);
+    assert_eq!(storage.approximate_count(.., false).unwrap(), 1000);
+    let size = storage
+        .approximate_size(&b"key_0100"[..]..&b"key_0200"[..], false)
+        .unwrap();
+    assert!(size >= total / 10 && size < total / 5);
+    let count = storage
+        .approximate_count(&b"key_0100"[..]..&b"key_0200"[..], true)
+        .unwrap();
+    assert!((100..200).contains(&count));
+    // The last block is counted, as the index does not know the last key of a table.
+    let count = storage.approximate_count(&b"key_2"[..].., true).unwrap();
+    assert!(count < 20);
+    assert_eq!(storage.approximate_count(..&b"key"[..], true).unwrap(), 0);
+}
+
+#[test]
+fn test_storage_suggest_split_keys() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions {
+        block_size: 256,
+        ..Default::default()
+    };
+    let storage = LsmStorage::open_with_options(&dir, options).unwrap();
+    // Two overlapping tables, where the second half of the keys has larger values.
+    for round in 0..2 {
+        for idx in (round..1000).step_by(2) {
+            let value = if idx < 500 { "v" } else { "large_value" };
+            storage
+                .put(format!("key_{:04}", idx).as_bytes(), value.as_bytes())
+                .unwrap();
+        }
+        storage.sync().unwrap();
+    }
+
+    let split_keys = storage.suggest_split_keys(.., 4).unwrap();
+    assert_eq!(split_keys.len(), 3);
+    assert!(split_keys.windows(2).all(|x| x[0] < x[1]));
+    // Each part has roughly a quarter of the data.
+    let total = storage.approximate_size(.., false).unwrap();
+    let mut bounds = vec![Bound::Unbounded];
+    bounds.extend(split_keys.iter().map(|key| Bound::Included(&key[..])));
+    bounds.push(Bound::Unbounded);
+    for part in bounds.windows(2) {
+        let upper = match part[1] {
+            Bound::Included(key) => Bound::Excluded(key),
+            bound => bound,
+        };
+        let size = storage.approximate_size((part[0], upper), false).unwrap();
+        assert!(
+            size > total / 6 && size < total / 3,
+            "{} of {}",
+            size,
+            total
+        );
+    }
+    // The larger values are in the second half of the keys, which holds more parts.
+    assert!(&split_keys[0][..] < b"key_0500".as_slice());
+    assert!(&split_keys[1][..] > b"key_0500".as_slice());
+
+    let split_keys = storage
+        .suggest_split_keys(&b"key_0100"[..]..&b"key_0200"[..], 2)
+        .unwrap();
+    assert_eq!(split_keys.len(), 1);
+    assert!(
+        &split_keys[0][..] > b"key_0100".as_slice() && &split_keys[0][..] < b"key_0200".as_slice()
+    );
+    assert!(storage.suggest_split_keys(.., 1).unwrap().is_empty());
+    assert!(storage
+        .suggest_split_keys(&b"key_2"[..].., 4)
+        .unwrap()
+        .is_empty());
+}
+
+fn num_of_ssts(dir: &Path) -> usize {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("sst".as_ref()))
+        .count()
+}
+
+#[test]
+fn test_storage_compact_range() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions {
+        block_size: 256,
+        target_sst_size: 4096,
+        ..Default::default()
+    };
+    let storage = LsmStorage::open_with_options(&dir, options).unwrap();
+    let key = |idx: usize| format!("key_{:04}", idx).into_bytes();
+    for round in 0..3 {
+        for idx in 0..1000 {
+            storage
+                .put(&key(idx), format!("value_{}_{}", round, idx).as_bytes())
+                .unwrap();
+        }
+        storage.sync().unwrap();
+    }
+    for idx in 100..900 {
+        storage.delete(&key(idx)).unwrap();
+    }
+    storage.sync().unwrap();
+    // A table outside the range, which is left in L0.
+    storage.put(b"other", b"value").unwrap();
+    storage.sync().unwrap();
+    assert_eq!(num_of_ssts(dir.path()), 5);
+    let size = storage.approximate_size(.., false).unwrap();
+
+    storage
+        .compact_range(Bound::Included(b"key_0100"), Bound::Excluded(b"key_0900"))
+        .unwrap();
+    assert!(storage.approximate_size(.., false).unwrap() < size / 3);
+    // The output is split into SSTs of the target size.
+    assert!(num_of_ssts(dir.path()) > 2);
+    assert_eq!(storage.get(b"other").unwrap(), Some(Bytes::from("value")));
+    for idx in 0..1000 {
+        let value = storage.get(&key(idx)).unwrap();
+        if (100..900).contains(&idx) {
+            assert_eq!(value, None);
+        } else {
+            assert_eq!(value, Some(Bytes::from(format!("value_2_{}", idx))));
+        }
+    }
+    let keys: Vec<&[u8]> = vec![b"key_0050", b"key_0500", b"key_0950", b"other"];
+    assert_eq!(
+        storage.multi_get(&keys).unwrap(),
+        vec![
+            Some(Bytes::from("value_2_50")),
+            None,
+            Some(Bytes::from("value_2_950")),
+            Some(Bytes::from("value")),
+        ]
+    );
+    let entries = storage
+        .scan_collect(&b"key_0098"[..]..=&b"key_0901"[..], usize::MAX)
+        .unwrap();
+    let expected: Vec<(Bytes, Bytes)> = [98, 99, 900, 901]
+        .into_iter()
+        .map(|idx| {
+            (
+                Bytes::from(key(idx)),
+                Bytes::from(format!("value_2_{}", idx)),
+            )
+        })
+        .collect();
+    assert_eq!(entries, expected);
+
+    // Newer writes shadow the compacted data.
+    storage.put(&key(0), b"new").unwrap();
+    storage.delete(&key(1)).unwrap();
+    storage.put(&key(500), b"new").unwrap();
+    storage.sync().unwrap();
+    assert_eq!(storage.get(&key(0)).unwrap(), Some(Bytes::from("new")));
+    assert_eq!(storage.get(&key(1)).unwrap(), None);
+    let entries = storage
+        .scan_collect(&key(0)[..]..=&key(2)[..], usize::MAX)
+        .unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].1, Bytes::from("new"));
+
+    // Compact everything again, including the table of the other key.
+    storage
+        .compact_range(Bound::Unbounded, Bound::Unbounded)
+        .unwrap();
+    assert_eq!(storage.get(&key(0)).unwrap(), Some(Bytes::from("new")));
+    assert_eq!(storage.get(&key(1)).unwrap(), None);
+    assert_eq!(storage.get(&key(500)).unwrap(), Some(Bytes::from("new")));
+    assert_eq!(storage.get(b"other").unwrap(), Some(Bytes::from("value")));
+    assert_eq!(storage.approximate_count(.., false).unwrap() as usize, 201);
+}
+
+#[test]
+fn test_storage_compact_range_trivial_move() {
+    let dir = tempdir().unwrap();
+    let storage = LsmStorage::open(&dir).unwrap();
+    // Keys written in order, where each table covers its own range.
+    for batch in 0..4 {
+        for idx in batch * 100..(batch + 1) * 100 {
+            storage
+                .put(format!("key_{:04}", idx).as_bytes(), b"value")
+                .unwrap();
+        }
+        storage.sync().unwrap();
+    }
+    let ssts = |dir: &Path| {
+        let mut names: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| name.to_string_lossy().ends_with(".sst"))
+            .collect();
+        names.sort();
+        names
+    };
+    let before = ssts(dir.path());
+    assert_eq!(before.len(), 4);
+    storage
+        .compact_range(Bound::Unbounded, Bound::Unbounded)
+        .unwrap();
+    // No table is rewritten.
+    assert_eq!(ssts(dir.path()), before);
+    assert_eq!(storage.scan_collect(.., usize::MAX).unwrap().len(), 400);
+
+    // An overlapping table is merged with the one it overlaps, while the others stay.
+    storage.put(b"key_0150", b"new").unwrap();
+    storage.sync().unwrap();
+    // A table with a tombstone is rewritten to drop it.
+    storage.delete(b"key_0350").unwrap();
+    storage.sync().unwrap();
+    storage
+        .compact_range(Bound::Unbounded, Bound::Unbounded)
+        .unwrap();
+    let after = ssts(dir.path());
+    assert_eq!(after.len(), 4);
+    assert!(after.contains(&before[0]) && after.contains(&before[2]));
+    assert!(!after.contains(&before[1]) && !after.contains(&before[3]));
+    assert_eq!(storage.get(b"key_0150").unwrap(), Some(Bytes::from("new")));
+    assert_eq!(storage.get(b"key_0350").unwrap(), None);
+    assert_eq!(
+        storage.get(b"key_0250").unwrap(),
+        Some(Bytes::from("value"))
+    );
+    assert_eq!(storage.scan_collect(.., usize::MAX).unwrap().len(), 399);
+    assert_eq!(storage.approximate_count(.., false).unwrap(), 399);
+}
+
+#[test]
+fn test_storage_compact_range_subcompactions() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions {
+        block_size: 256,
+        max_subcompactions: 4,
+        ..Default::default()
+    };
+    let storage = LsmStorage::open_with_options(&dir, options).unwrap();
+    let key = |idx: usize| format!("key_{:04}", idx).into_bytes();
+    for round in 0..3 {
+        for idx in (round..2000).step_by(2) {
+            storage
+                .put(&key(idx), format!("value_{}_{}", round, idx).as_bytes())
+                .unwrap();
+        }
+        storage.sync().unwrap();
+    }
+    for idx in (0..2000).step_by(7) {
+        storage.delete(&key(idx)).unwrap();
+    }
+    storage.sync().unwrap();
+
+    storage
+        .compact_range(Bound::Unbounded, Bound::Unbounded)
+        .unwrap();
+    // Each subcompaction writes its own SST.
+    assert_eq!(num_of_ssts(dir.path()), 4);
+    let entries = storage.scan_collect(.., usize::MAX).unwrap();
+    let expected: Vec<(Bytes, Bytes)> = (0..2000)
+        .filter(|idx| idx % 7 != 0)
+        .map(|idx| {
+            let round = if idx % 2 == 0 { 2 } else { 1 };
+            (
+                Bytes::from(key(idx)),
+                Bytes::from(format!("value_{}_{}", round, idx)),
+            )
+        })
+        .collect();
+    assert_eq!(entries, expected);
+    for (key, value) in expected.iter().step_by(13) {
+        assert_eq!(storage.get(key).unwrap().as_ref(), Some(value));
+    }
+}
+
+#[test]
+fn test_storage_write_stall() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions {
+        write_stall: WriteStallOptions {
+            l0_slowdown_trigger: Some(1),
+            l0_stop_trigger: Some(2),
+            slowdown_delay: Duration::from_millis(5),
+            stop_timeout: Duration::from_millis(200),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let storage = LsmStorage::open_with_options(&dir, options).unwrap();
+    storage.put(b"1", b"1").unwrap();
+    assert_eq!(storage.write_stall_stats(), WriteStallStats::default());
+
+    storage.sync().unwrap();
+    assert_eq!(storage.write_stall_stats().state, WriteStallState::Delayed);
+    storage.put(b"2", b"2").unwrap();
+    storage.delete(b"1").unwrap();
+    let stats = storage.write_stall_stats();
+    assert_eq!(stats.num_delays, 2);
+    assert!(stats.delay_duration >= Duration::from_millis(10));
+
+    // Writes time out while stopped.
+    storage.sync().unwrap();
+    assert_eq!(storage.write_stall_stats().state, WriteStallState::Stopped);
+    assert!(storage.put(b"3", b"3").is_err());
+    assert!(!storage.put_if_absent(b"3", b"3").is_ok_and(|x| x));
+    let stats = storage.write_stall_stats();
+    assert_eq!((stats.num_stops, stats.num_stop_timeouts), (2, 2));
+    assert!(stats.stop_duration >= Duration::from_millis(400));
+    assert_eq!(storage.get(b"3").unwrap(), None);
+
+    // A compaction resumes a stopped write.
+    std::thread::scope(|scope| {
+        let handle = scope.spawn(|| storage.put(b"3", b"3"));
+        while storage.write_stall_stats().num_stops < 3 {
+            std::thread::yield_now();
+        }
+        storage
+            .compact_range(Bound::Unbounded, Bound::Unbounded)
+            .unwrap();
+        handle.join().unwrap().unwrap();
+    });
+    let stats = storage.write_stall_stats();
+    assert_eq!(stats.state, WriteStallState::Normal);
+    assert_eq!((stats.num_stops, stats.num_stop_timeouts), (3, 2));
+    assert_eq!(storage.get(b"3").unwrap(), Some(Bytes::from("3")));
+}
+
+#[test]
+fn test_storage_rate_limiter() {
+    let dir = tempdir().unwrap();
+    let rate_limiter = Arc::new(RateLimiter::new(0));
+    let options = LsmStorageOptions {
+        rate_limiter: Some(rate_limiter.clone()),
+        ..Default::default()
+    };
+    let storage = LsmStorage::open_with_options(&dir, options).unwrap();
+    for idx in 0..1000 {
+        storage
+            .put(format!("key_{:04}", idx).as_bytes(), &[b'x'; 100])
+            .unwrap();
+    }
+    // The SST of about 100KB takes about 200ms at 500KB/s.
+    rate_limiter.set_bytes_per_sec(500_000);
+    let start = Instant::now();
+    storage.sync().unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    rate_limiter.set_bytes_per_sec(0);
+    assert_eq!(
+        storage.get(b"key_0500").unwrap(),
+        Some(Bytes::copy_from_slice(&[b'x'; 100]))
+    );
+}
+
+#[test]
+fn test_storage_durable_files() {
+    let root = tempdir().unwrap();
+    let dir = root.path().join("a/b");
+    let storage = LsmStorage::open(&dir).unwrap();
+    storage.put(b"1", b"1").unwrap();
+    storage.sync().unwrap();
+
+    // Another storage cannot open the directory, and leaves the files of the first one alone.
+    let temp_path = dir.join("2.sst.tmp");
+    std::fs::write(&temp_path, b"partial").unwrap();
+    assert!(LsmStorage::open(&dir).is_err());
+    assert!(temp_path.exists());
+    std::fs::remove_file(&temp_path).unwrap();
+    drop(storage);
+    let has_temp_files = |dir: &Path| {
+        std::fs::read_dir(dir).unwrap().any(|entry| {
+            entry
+                .unwrap()
+                .path()
+                .to_string_lossy()
+                .ends_with(TEMP_SUFFIX)
+        })
+    };
+    assert!(!has_temp_files(&dir));
+    assert_eq!(num_of_ssts(&dir), 1);
+
+    // A file left behind by a crashed writer is removed on open.
+    std::fs::write(&temp_path, b"partial").unwrap();
+    LsmStorage::open(&dir).unwrap();
+    assert!(!has_temp_files(&dir));
+}